@@ -1,7 +1,198 @@
 use tamil_font_converter_rs::*;
+use tamil_font_converter_rs::verify::verify;
+use tamil_font_converter_rs::chart::{encoding_chart, export_chart, ChartFormat};
+use tamil_font_converter_rs::encoding::Encoding;
+use tamil_font_converter_rs::epub::convert_epub;
+use tamil_font_converter_rs::fonts::{Direction, FontConversion};
+use tamil_font_converter_rs::csv::{convert_csv, Columns};
+use tamil_font_converter_rs::docx::convert_docx;
+use tamil_font_converter_rs::html::convert_html;
+use tamil_font_converter_rs::localization::{convert_json, convert_po, KeyFilter};
+use tamil_font_converter_rs::markup::{convert_latex, convert_markdown};
+use tamil_font_converter_rs::odt::convert_odt;
+use tamil_font_converter_rs::pdf::extract_pdf_text;
+use tamil_font_converter_rs::repair::repair_visual_order;
+use tamil_font_converter_rs::rtf::convert_rtf;
+use tamil_font_converter_rs::subtitles::convert_subtitles;
+use tamil_font_converter_rs::svg::convert_svg;
+use tamil_font_converter_rs::xlsx::convert_xlsx;
+use std::io::{Read, Write};
+
+const USAGE: &str = "usage: main [COMMAND [OPTIONS] [FILE]]
+  (no command)   print the alphabet converted to stmzh
+  encode [FILE]  convert FILE (or stdin) from unicode to the legacy encoding
+  decode [FILE]  convert FILE (or stdin) from the legacy encoding to unicode
+                 (U+F0XX glyphs, or 8-bit text: raw bytes or read as Windows-1252)
+                 documents (.docx, .odt, .rtf, .html, .epub, .svg) only have the text set in the legacy font
+                 (or in a unicode tamil font, when encoding) converted
+                 subtitles (.srt, .vtt) only have their cue text converted
+                 markdown and latex (.md, .tex) only have their prose converted, not code,
+                 urls, commands or math
+                 translations (.po, .json) have their msgstr and string values matching
+                 --keys converted, placeholders (%s, {name}) kept
+                 tables (.csv, .tsv) have the --columns converted (all if none is given)
+                 below their header row,
+                 spreadsheets (.xlsx) the --columns and the cells in the legacy font
+                 decoding a .pdf prints its text, read font by font
+  repair [FILE]  move the vowel signs of FILE (or stdin) typed before their consonant (ெக)
+                 after it (கெ), listing the fixes on stderr
+  verify [FILE]  check that FILE (or stdin) survives unicode -> legacy -> unicode
+  chart FORMAT [FONT_FAMILY [FONT_URL]]
+                 print the mapping chart of the --encoding as csv, json or html
+                 (html glyphs use FONT_FAMILY, default the encoding's font, loaded from FONT_URL)
+options:
+  --encoding MAPPING  legacy encoding described by a .toml or .json mapping file (default: stmzh)
+  --low-byte          legacy text uses the bytes of the symbol font (0xXX) instead of U+F0XX:
+                      encode writes an 8-bit file, and decode reads its raw bytes
+  --pre-reform        encode writes the old ligatures (லை, ணா, றா ...) with the glyphs the
+                      mapping file lists under [pre_reform] (decode always reads them)
+  --font NAME         unicode font given to decoded text in documents (default: Latha)
+  --columns LIST      columns of tables to convert: header names, numbers or letters (Name,3,F)
+  --no-header         the first row of tables (.csv, .tsv) is converted too: by default it is
+                      a header, kept as it is
+  --keys PATTERNS      keys of the translations to convert: json key paths or po msgctxt/msgid,
+                      * matching anything (menu.*,title)
+  --output FILE       write to FILE instead of stdout";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2)
+}
+
+//Removes `--name VALUE` from args and returns VALUE
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let position = args.iter().position(|a| a == name)?;
+    if position + 1 >= args.len() {
+        fail(&format!("{} needs a value", name));
+    }
+    args.remove(position);
+    Some(args.remove(position))
+}
+
+//Removes the flag `name` from args, and tells whether it was there
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let position = args.iter().position(|a| a == name);
+    position.map(|p| args.remove(p)).is_some()
+}
+
+//Contents of the file given as argument, or stdin if there is none
+fn read_input(path: Option<&String>) -> std::io::Result<Vec<u8>> {
+    match path {
+        Some(path) => std::fs::read(path),
+        None => {
+            let mut input = Vec::new();
+            std::io::stdin().read_to_end(&mut input)?;
+            Ok(input)
+        }
+    }
+}
+
+//Documents mixing fonts, told by the extension of their file
+fn convert_document(path: &str, input: &[u8], columns: &Columns, header: bool, keys: &KeyFilter, conversion: &FontConversion) -> Option<Result<Vec<u8>, String>> {
+    let extension = std::path::Path::new(path).extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "docx" => Some(convert_docx(input, conversion)),
+        "odt" => Some(convert_odt(input, conversion)),
+        "rtf" => Some(convert_rtf(input, conversion)),
+        "html" | "htm" => Some(convert_html(input, conversion)),
+        "epub" => Some(convert_epub(input, conversion)),
+        "svg" => Some(convert_svg(input, conversion)),
+        "srt" | "vtt" => Some(convert_subtitles(input, conversion)),
+        "md" | "markdown" => Some(convert_markdown(input, conversion)),
+        "tex" => Some(convert_latex(input, conversion)),
+        "po" => Some(convert_po(input, keys, conversion)),
+        "json" => Some(convert_json(input, keys, conversion)),
+        "csv" => Some(convert_csv(input, ',', columns, header, conversion)),
+        "tsv" => Some(convert_csv(input, '\t', columns, header, conversion)),
+        "xlsx" => Some(convert_xlsx(input, columns, conversion)),
+        "pdf" if conversion.direction == Direction::Decode => {
+            Some(extract_pdf_text(input, std::slice::from_ref(conversion)).map(String::into_bytes))
+        }
+        "pdf" => Some(Err("pdf files can only be decoded".to_string())),
+        _ => None,
+    }
+}
 
 fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut encoding = match take_option(&mut args, "--encoding") {
+        Some(path) => Encoding::from_file(path).unwrap_or_else(|e| fail(&e)),
+        None => Encoding::stmzh(),
+    };
+    let pre_reform = take_flag(&mut args, "--pre-reform");
+    if pre_reform && encoding.pre_reform.is_empty() {
+        eprintln!("{} has no pre-reform glyphs: the modern spelling is written", encoding.name);
+    }
+    encoding.use_pre_reform = pre_reform;
+    let low_byte = take_flag(&mut args, "--low-byte");
+    let font = take_option(&mut args, "--font");
+    let output = take_option(&mut args, "--output");
+    let columns = take_option(&mut args, "--columns").map(|c| Columns::parse(&c)).unwrap_or_default();
+    let header = !take_flag(&mut args, "--no-header");
+    let keys = take_option(&mut args, "--keys").map(|k| KeyFilter::parse(&k)).unwrap_or_default();
+    let encode = |s: &str| if low_byte { encoding.encode_low_byte(s) } else { encoding.encode(s) };
+    let decode = |s: &str| if low_byte { encoding.decode_low_byte(s) } else { encoding.decode(s) };
+    let input = || read_input(args.get(1)).unwrap_or_else(|e| fail(&e.to_string()));
+    let text_input = || String::from_utf8(input()).unwrap_or_else(|e| fail(&e.to_string()));
+    let conversion = |direction| {
+        let mut conversion = FontConversion::new(encoding.clone(), direction);
+        conversion.low_byte = low_byte;
+        conversion.pre_reform = pre_reform;
+        if let Some(font) = &font {
+            conversion.unicode_font = font.clone();
+        }
+        conversion
+    };
+    let document = |direction| {
+        let path = args.get(1)?;
+        convert_document(path, &input(), &columns, header, &keys, &conversion(direction)).map(|r| r.unwrap_or_else(|e| fail(&e)))
+    };
+
+    let result = match args.first().map(String::as_str) {
+        None => {
+            print_alphabet();
+            return;
+        }
+        Some("encode") => document(Direction::Encode).unwrap_or_else(|| {
+            if low_byte {
+                encoding.encode_bytes(&text_input())
+            } else {
+                encoding.encode(&text_input()).into_bytes()
+            }
+        }),
+        Some("decode") => document(Direction::Decode)
+            .unwrap_or_else(|| encoding.decode_file(&input(), low_byte).into_bytes()),
+        Some("repair") => {
+            let (repaired, fixes) = repair_visual_order(&text_input());
+            for fix in &fixes {
+                eprintln!("{}", fix);
+            }
+            repaired.into_bytes()
+        }
+        Some("verify") => {
+            let report = verify(&text_input(), encode, decode);
+            print!("{}", report);
+            if !report.is_ok() {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("chart") => {
+            let format = args.get(1).ok_or_else(|| USAGE.to_string())
+                .and_then(|f| f.parse::<ChartFormat>())
+                .unwrap_or_else(|e| fail(&e));
+            let font_family = args.get(2).or_else(|| encoding.fonts.first()).unwrap_or(&encoding.name);
+            export_chart(&encoding_chart(&encoding), format, font_family, args.get(3).map(String::as_str)).into_bytes()
+        }
+        Some(_) => fail(USAGE),
+    };
+    match output {
+        Some(path) => std::fs::write(path, result),
+        None => std::io::stdout().write_all(&result),
+    }.unwrap_or_else(|e| fail(&e.to_string()));
+}
 
+fn print_alphabet() {
     println!("{}", convert_unic_stmzh("
             அகர முதல எழுத்தெல்லாம் ஆதி பகவன் முதற்றே உலகு
 
@@ -0,0 +1,257 @@
+use crate::{
+    conv_composed_entity, conv_marked_special_entity, conv_special_entity, convert_unic_stmzh, fallback,
+    unic_stmzh_map_char_char, unic_stmzh_map_tuple_char,
+    CONSONANTS, DIGITS, NON_RIDING_MARKS, RIDING_MARKS, SIGNS, VOWELS,
+};
+use crate::csv::write_field;
+use crate::encoding::Encoding;
+use crate::supplement::{self, FRACTIONS};
+use serde::Serialize;
+use std::fmt::Write;
+use std::str::FromStr;
+
+//One letter of the chart: its Unicode text and the glyphs drawing it in the legacy font
+#[derive(Debug)]
+pub struct ChartEntry {
+    pub category: &'static str,
+    pub unicode: String,
+    pub glyphs: String,
+}
+
+//A row of the JSON chart
+#[derive(Serialize)]
+struct JsonEntry<'a> {
+    category: &'a str,
+    unicode: &'a str,
+    unicode_code_points: String,
+    glyphs: &'a str,
+    glyph_code_points: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChartFormat {
+    Csv,
+    Json,
+    Html,
+}
+
+impl FromStr for ChartFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ChartFormat::Csv),
+            "json" => Ok(ChartFormat::Json),
+            "html" => Ok(ChartFormat::Html),
+            _ => Err(format!("unknown chart format `{}` (expected csv, json or html)", s)),
+        }
+    }
+}
+
+//Every letter convert_unic_stmzh knows, in alphabet order:
+//vowels, consonants, riding marks (UNIC_STMZH_MAP_TUPLE_CHAR), composed entities, special entities,
+//then the letters without a glyph of their own, drawn as their fallback.
+pub fn stmzh_chart() -> Vec<ChartEntry> {
+    let unic_stmzh_map_char_char = unic_stmzh_map_char_char();
+    let unic_stmzh_map_tuple_char = unic_stmzh_map_tuple_char();
+    let mut chart = Vec::new();
+    let mut push = |category, unicode: String, glyphs: String| chart.push(ChartEntry { category, unicode, glyphs });
+
+    for c in VOWELS.chars() {
+        push("vowel", c.to_string(), unic_stmzh_map_char_char[&c].to_string());
+    }
+    for c in CONSONANTS.chars() {
+        if let Some(s) = unic_stmzh_map_char_char.get(&c) {
+            push("consonant", c.to_string(), s.to_string());
+        }
+    }
+    for m in RIDING_MARKS.chars() {
+        for c in CONSONANTS.chars() {
+            if let Some(s) = unic_stmzh_map_tuple_char.get(&(c, m)) {
+                push("separate", [c, m].iter().collect(), s.to_string());
+            }
+        }
+    }
+    for m in NON_RIDING_MARKS.chars() {
+        for c in CONSONANTS.chars() {
+            if let Some(&s) = unic_stmzh_map_char_char.get(&c) {
+                push("composed", [c, m].iter().collect(), conv_composed_entity(s, m));
+            }
+        }
+    }
+    for ustring in &["ஸ்ரீ", "க்ஷ"] {
+        push("special", ustring.to_string(), conv_special_entity(ustring).unwrap());
+    }
+    for m in RIDING_MARKS.chars().chain(NON_RIDING_MARKS.chars()) {
+        if let Some(s) = conv_marked_special_entity("க்ஷ", m) {
+            push("marked special", format!("க்ஷ{}", m), s);
+        }
+    }
+    for c in CONSONANTS.chars().chain(SIGNS.chars()).chain(DIGITS.chars()).chain(FRACTIONS).chain(supplement::SIGNS) {
+        if fallback(c).is_some() {
+            push("fallback", c.to_string(), convert_unic_stmzh(&c.to_string()));
+        }
+    }
+    chart
+}
+
+//Same for a mapping file: the letters of its tables, in the same order, then its pre-reform
+//ligatures and symbols
+pub fn encoding_chart(encoding: &Encoding) -> Vec<ChartEntry> {
+    let mut chart = Vec::new();
+    let mut push = |category, unicode: String, glyphs: String| chart.push(ChartEntry { category, unicode, glyphs });
+    let encode = |unicode: &str| encoding.encode_orthography(unicode, false);
+
+    for c in VOWELS.chars().filter(|c| encoding.vowels.contains_key(c)) {
+        push("vowel", c.to_string(), encode(&c.to_string()));
+    }
+    for c in CONSONANTS.chars().filter(|c| encoding.consonants.contains_key(c)) {
+        push("consonant", c.to_string(), encode(&c.to_string()));
+    }
+    for m in RIDING_MARKS.chars() {
+        for c in CONSONANTS.chars() {
+            let unicode: String = [c, m].iter().collect();
+            if m == '\u{0bcd}' && encoding.pulli.contains_key(&c) || encoding.riding.contains_key(&unicode) {
+                let glyphs = encode(&unicode);
+                push("separate", unicode, glyphs);
+            }
+        }
+    }
+    for m in NON_RIDING_MARKS.chars().filter(|m| encoding.marks.contains_key(m)) {
+        for c in CONSONANTS.chars().filter(|c| encoding.consonants.contains_key(c)) {
+            let unicode: String = [c, m].iter().collect();
+            push("composed", unicode.clone(), encode(&unicode));
+        }
+    }
+    for ustring in ["ஸ்ரீ", "க்ஷ"].iter().filter(|s| encoding.special.contains_key(**s)) {
+        push("special", ustring.to_string(), encode(ustring));
+    }
+    for m in RIDING_MARKS.chars().chain(NON_RIDING_MARKS.chars()) {
+        let unicode = format!("க்ஷ{}", m);
+        if encoding.special.contains_key(&unicode) || encoding.special.contains_key("க்ஷ") && encoding.marks.contains_key(&m) {
+            let glyphs = encode(&unicode);
+            push("marked special", unicode, glyphs);
+        }
+    }
+    for c in CONSONANTS.chars().chain(SIGNS.chars()).chain(DIGITS.chars()).chain(FRACTIONS).chain(supplement::SIGNS) {
+        if fallback(c).is_some() && !encoding.has_glyph(c) {
+            push("fallback", c.to_string(), encode(&c.to_string()));
+        }
+    }
+    for m in NON_RIDING_MARKS.chars() {
+        for c in CONSONANTS.chars() {
+            let unicode: String = [c, m].iter().collect();
+            if encoding.pre_reform.contains_key(&unicode) {
+                let glyphs = encoding.encode_orthography(&unicode, true);
+                push("pre-reform", unicode, glyphs);
+            }
+        }
+    }
+    let mut symbols: Vec<_> = encoding.symbols.iter().collect();
+    symbols.sort();
+    for (c, glyphs) in symbols {
+        push("symbol", c.to_string(), glyphs.clone());
+    }
+    chart
+}
+
+//"U+0B95 U+0BBE"
+fn code_points(s: &str) -> String {
+    s.chars().map(|c| format!("U+{:04X}", c as u32)).collect::<Vec<_>>().join(" ")
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+pub fn chart_to_csv(chart: &[ChartEntry]) -> String {
+    let mut res = String::from("category,unicode,unicode_code_points,glyphs,glyph_code_points\n");
+    for entry in chart {
+        let fields = [entry.category, &entry.unicode, &code_points(&entry.unicode), &entry.glyphs, &code_points(&entry.glyphs)];
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                res.push(',');
+            }
+            write_field(field, false, ',', &mut res);
+        }
+        res.push('\n');
+    }
+    res
+}
+
+pub fn chart_to_json(chart: &[ChartEntry]) -> String {
+    let rows: Vec<JsonEntry> = chart.iter().map(|entry| JsonEntry {
+        category: entry.category,
+        unicode: &entry.unicode,
+        unicode_code_points: code_points(&entry.unicode),
+        glyphs: &entry.glyphs,
+        glyph_code_points: code_points(&entry.glyphs),
+    }).collect();
+    serde_json::to_string_pretty(&rows).unwrap() + "\n"
+}
+
+//Printable page: glyphs are drawn with font_family, loaded from font_url when given.
+pub fn chart_to_html(chart: &[ChartEntry], font_family: &str, font_url: Option<&str>) -> String {
+    let mut res = String::new();
+    res.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Tamil mapping chart</title>\n<style>\n");
+    if let Some(url) = font_url {
+        writeln!(res, "@font-face {{ font-family: \"{}\"; src: url(\"{}\"); }}", escape_html(font_family), escape_html(url)).unwrap();
+    }
+    writeln!(res, ".glyphs {{ font-family: \"{}\"; }}", escape_html(font_family)).unwrap();
+    res.push_str("table { border-collapse: collapse; }\ntd, th { border: 1px solid #999; padding: 0.2em 0.5em; }\n");
+    res.push_str("</style>\n</head>\n<body>\n<table>\n");
+    res.push_str("<tr><th>category</th><th>unicode</th><th>code points</th><th>glyphs</th><th>glyph codes</th></tr>\n");
+    for entry in chart {
+        writeln!(res, "<tr><td>{}</td><td>{}</td><td>{}</td><td class=\"glyphs\">{}</td><td>{}</td></tr>",
+            entry.category, escape_html(&entry.unicode), code_points(&entry.unicode),
+            escape_html(&entry.glyphs), code_points(&entry.glyphs)).unwrap();
+    }
+    res.push_str("</table>\n</body>\n</html>\n");
+    res
+}
+
+pub fn export_chart(chart: &[ChartEntry], format: ChartFormat, font_family: &str, font_url: Option<&str>) -> String {
+    match format {
+        ChartFormat::Csv => chart_to_csv(chart),
+        ChartFormat::Json => chart_to_json(chart),
+        ChartFormat::Html => chart_to_html(chart, font_family, font_url),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chart_covers_all_tables() {
+        let chart = stmzh_chart();
+        let count = |category| chart.iter().filter(|e| e.category == category).count();
+        assert_eq!(count("vowel"), VOWELS.chars().count());
+        assert_eq!(count("separate"), unic_stmzh_map_tuple_char().len());
+        assert_eq!(count("composed"), count("consonant") * NON_RIDING_MARKS.chars().count());
+        assert_eq!(count("fallback"), 47); //ஶ, ௐ, ௦-௯, 20 fractions, 15 measures
+        let csv = chart_to_csv(&chart);
+        assert!(csv.contains("composed,கொ,U+0B95 U+0BCA,\u{f0d8}\u{f0ef}\u{f056},U+F0D8 U+F0EF U+F056\n"));
+        let same = |a: &ChartEntry, b: &ChartEntry| (a.category, &a.unicode, &a.glyphs) == (b.category, &b.unicode, &b.glyphs);
+        let from_encoding = encoding_chart(&Encoding::stmzh());
+        assert!(chart.len() == from_encoding.len() && chart.iter().zip(&from_encoding).all(|(a, b)| same(a, b)));
+
+        let old = Encoding::from_json_str(r#"{"name": "Old", "consonants": {"ல": "l"}, "marks": {"ை": {"prefix": "ai"}},
+            "pre_reform": {"லை": "L"}, "symbols": {"௧": "1"}}"#).unwrap();
+        let old_chart = encoding_chart(&old);
+        let rows: Vec<(&str, &str, &str)> = old_chart.iter()
+            .filter(|e| e.category != "fallback")
+            .map(|e| (e.category, e.unicode.as_str(), e.glyphs.as_str()))
+            .collect();
+        assert_eq!(rows, [("consonant", "ல", "l"), ("composed", "லை", "ail"), ("pre-reform", "லை", "L"), ("symbol", "௧", "1")]);
+    }
+
+    #[test]
+    fn quotes_csv_and_escapes_json() {
+        let chart = [ChartEntry { category: "symbol", unicode: "\"a,b\"\n".to_string(), glyphs: "\\".to_string() }];
+        assert!(chart_to_csv(&chart).ends_with("\nsymbol,\"\"\"a,b\"\"\n\",U+0022 U+0061 U+002C U+0062 U+0022 U+000A,\\,U+005C\n"));
+        let json: serde_json::Value = serde_json::from_str(&chart_to_json(&chart)).unwrap();
+        assert_eq!(json[0]["unicode"], "\"a,b\"\n");
+        assert_eq!(json[0]["glyphs"], "\\");
+    }
+}
@@ -0,0 +1,202 @@
+use crate::fonts::FontConversion;
+use std::collections::HashMap;
+
+//"'Noto Sans Tamil', STMZH, serif" -> ["Noto Sans Tamil", "STMZH", "serif"]
+pub fn font_families(value: &str) -> Vec<String> {
+    value.split(',')
+        .map(|f| f.trim().trim_matches(|c| c == '"' || c == '\'').trim().to_string())
+        .filter(|f| !f.is_empty())
+        .collect()
+}
+
+//Value of the font-family property in declarations ("color: red; font-family: STMZH")
+pub fn font_family(declarations: &str) -> Option<String> {
+    declarations.split(';').rev().find_map(|declaration| {
+        let (property, value) = declaration.split_once(':')?;
+        if property.trim().eq_ignore_ascii_case("font-family") {
+            Some(value.trim().trim_end_matches("!important").trim().to_string())
+        } else {
+            None
+        }
+    })
+}
+
+//Families quoted when they need to be
+fn family_list(families: &[String]) -> String {
+    families.iter()
+        .map(|f| if f.contains(' ') { format!("'{}'", f) } else { f.clone() })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+//Source fonts replaced by the target font in a font-family value (None if there is none)
+pub fn rewrite_font_family_value(value: &str, conversion: &FontConversion) -> Option<String> {
+    let families = font_families(value);
+    if !families.iter().any(|f| conversion.is_source_font(f)) {
+        return None;
+    }
+    let mut rewritten: Vec<String> = Vec::new();
+    for family in families {
+        let family = if conversion.is_source_font(&family) { conversion.target_font().to_string() } else { family };
+        if !rewritten.contains(&family) {
+            rewritten.push(family);
+        }
+    }
+    Some(family_list(&rewritten))
+}
+
+//Source fonts replaced by the target font in every font-family declaration of css
+//(a style attribute or a style sheet). Everything else is kept as is.
+pub fn rewrite_font_families(css: &str, conversion: &FontConversion) -> String {
+    let mut output = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = find_ignore_case(rest, "font-family") {
+        let after = &rest[start + "font-family".len()..];
+        let colon = after.len() - after.trim_start().len();
+        if !after[colon..].starts_with(':') {
+            output.push_str(&rest[..start + "font-family".len()]);
+            rest = after;
+            continue;
+        }
+        let value_start = start + "font-family".len() + colon + 1;
+        let value_len = rest[value_start..].find([';', '}']).unwrap_or(rest.len() - value_start);
+        let value = &rest[value_start..value_start + value_len];
+        output.push_str(&rest[..value_start]);
+        match rewrite_font_family_value(value.trim_end().trim_end_matches("!important"), conversion) {
+            Some(rewritten) => {
+                let leading = &value[..value.len() - value.trim_start().len()];
+                let trailing = &value[value.trim_end().len()..];
+                let important = if value.trim_end().ends_with("!important") { " !important" } else { "" };
+                output.push_str(leading);
+                output.push_str(&rewritten);
+                output.push_str(important);
+                output.push_str(trailing);
+            }
+            None => output.push_str(value),
+        }
+        rest = &rest[value_start + value_len..];
+    }
+    output.push_str(rest);
+    output
+}
+
+//@font-face rules declaring a source font: their src, the file of the legacy font, is
+//replaced by the target font installed on the reader's system
+fn rewrite_font_faces(css: &str, conversion: &FontConversion) -> String {
+    let mut output = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = find_ignore_case(rest, "@font-face") {
+        let (block_start, block_end) = match (rest[start..].find('{'), rest[start..].find('}')) {
+            (Some(open), Some(close)) if open < close => (start + open + 1, start + close),
+            _ => break,
+        };
+        output.push_str(&rest[..block_start]);
+        let block = &rest[block_start..block_end];
+        if font_family(block).is_some_and(|family| font_families(&family).iter().any(|f| conversion.is_source_font(f))) {
+            let target = family_list(&[conversion.target_font().to_string()]);
+            let declarations: Vec<String> = block.split(';').map(|declaration| {
+                match declaration.split_once(':') {
+                    Some((property, _)) if property.trim().eq_ignore_ascii_case("src") => {
+                        format!("{}: local({}){}", property, target, &declaration[declaration.trim_end().len()..])
+                    }
+                    _ => declaration.to_string(),
+                }
+            }).collect();
+            output.push_str(&declarations.join(";"));
+        } else {
+            output.push_str(block);
+        }
+        rest = &rest[block_end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+//Style sheet with its @font-face rules and font-family declarations of source fonts
+//changed to the target font
+pub fn rewrite_style_sheet(css: &str, conversion: &FontConversion) -> String {
+    rewrite_font_families(&rewrite_font_faces(css, conversion), conversion)
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack.to_ascii_lowercase().find(needle)
+}
+
+//font-family of the simple rules of a style sheet: by element name (p), class (.tamil) or id (#title)
+#[derive(Debug, Default, Clone)]
+pub struct FontRules {
+    pub elements: HashMap<String, String>,
+    pub classes: HashMap<String, String>,
+    pub ids: HashMap<String, String>,
+}
+
+impl FontRules {
+    //Rules other than `selector, selector { declarations }` with simple selectors are ignored
+    pub fn add_style_sheet(&mut self, css: &str) {
+        let css = strip_comments(css);
+        for rule in css.split('}') {
+            let (selectors, declarations) = match rule.split_once('{') {
+                Some(rule) => rule,
+                None => continue,
+            };
+            let family = match font_family(declarations) {
+                Some(family) => family,
+                None => continue,
+            };
+            for selector in selectors.split(',').map(str::trim) {
+                if selector.starts_with('@') || selector.contains(|c: char| c.is_whitespace() || c == '>' || c == ':' || c == '[') {
+                    continue;
+                }
+                if let Some(class) = selector.rsplit_once('.').map(|(_, class)| class) {
+                    self.classes.insert(class.to_string(), family.clone());
+                } else if let Some(id) = selector.strip_prefix('#') {
+                    self.ids.insert(id.to_string(), family.clone());
+                } else {
+                    self.elements.insert(selector.to_ascii_lowercase(), family.clone());
+                }
+            }
+        }
+    }
+
+    //font-family the rules give to an element (id first, then class, then element name)
+    pub fn font_family(&self, element: &str, id: Option<&str>, classes: Option<&str>) -> Option<&String> {
+        id.and_then(|id| self.ids.get(id))
+            .or_else(|| classes.and_then(|c| c.split_whitespace().rev().find_map(|c| self.classes.get(c))))
+            .or_else(|| self.elements.get(&element.to_ascii_lowercase()))
+    }
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut output = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        output.push_str(&rest[..start]);
+        rest = rest[start..].find("*/").map_or("", |end| &rest[start + end + 2..]);
+    }
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::fonts::Direction;
+
+    #[test]
+    fn rewrites_font_families() {
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        assert_eq!(rewrite_font_families("color: red; font-family: 'STMZH', serif; font-size: 12pt", &conversion),
+            "color: red; font-family: Latha, serif; font-size: 12pt");
+        assert_eq!(rewrite_font_families(".t { FONT-FAMILY:STMZH !important }\np { font-family: Arial }", &conversion),
+            ".t { FONT-FAMILY:Latha !important }\np { font-family: Arial }");
+        assert_eq!(rewrite_style_sheet("@font-face { font-family: STMZH; src: url(../fonts/stmzh.ttf) }", &conversion),
+            "@font-face { font-family: Latha; src: local(Latha) }");
+
+        let mut rules = FontRules::default();
+        rules.add_style_sheet("/* tamil */ .tamil, h1 { font-family: STMZH } div p { font-family: Arial }");
+        assert_eq!(rules.font_family("span", None, Some("big tamil")).map(String::as_str), Some("STMZH"));
+        assert_eq!(rules.font_family("H1", None, None).map(String::as_str), Some("STMZH"));
+        assert_eq!(rules.font_family("p", None, None), None);
+    }
+}
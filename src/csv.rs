@@ -0,0 +1,189 @@
+use crate::fonts::FontConversion;
+use crate::low_byte::bytes_to_low_byte;
+
+//Columns to convert, as given by the user: header names, numbers from 1, or spreadsheet
+//letters (A, B... AA)
+#[derive(Debug, Clone, Default)]
+pub struct Columns(pub Vec<String>);
+
+//"C" -> 2, "AA" -> 26
+fn column_letters_index(letters: &str) -> Option<usize> {
+    if letters.is_empty() || letters.len() > 3 || !letters.bytes().all(|b| b.is_ascii_uppercase()) {
+        return None;
+    }
+    Some(letters.bytes().fold(0, |index, b| index * 26 + (b - b'A' + 1) as usize) - 1)
+}
+
+//Index of the column of a cell reference ("B12" -> 1)
+pub fn cell_column(reference: &str) -> Option<usize> {
+    column_letters_index(reference.trim_end_matches(|c: char| c.is_ascii_digit()))
+}
+
+impl Columns {
+    //"Name, 3, F"
+    pub fn parse(spec: &str) -> Self {
+        Columns(spec.split(',').map(str::trim).filter(|c| !c.is_empty()).map(str::to_string).collect())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    //Are some columns given by their header name
+    pub fn uses_header(&self, header: &[String]) -> bool {
+        self.0.iter().any(|c| header.iter().any(|h| h.trim() == c))
+    }
+
+    fn index(column: &str, header: &[String]) -> Option<usize> {
+        header.iter().position(|h| h.trim() == column)
+            .or_else(|| column.parse::<usize>().ok().filter(|n| *n > 0).map(|n| n - 1))
+            .or_else(|| column_letters_index(column))
+    }
+
+    //Indexes (from 0) of the columns, header names first
+    pub fn resolve(&self, header: &[String]) -> Result<Vec<usize>, String> {
+        self.0.iter().map(|column| Self::index(column, header).ok_or_else(|| format!("no column {}", column))).collect()
+    }
+
+    //Same, leaving out the columns not found
+    pub fn find(&self, header: &[String]) -> Vec<usize> {
+        self.0.iter().filter_map(|column| Self::index(column, header)).collect()
+    }
+}
+
+struct Field {
+    text: String, //unquoted
+    quoted: bool,
+}
+
+//Reads a field at the beginning of input, and returns it with its length and the separator
+//ending it (delimiter, line ending, or "" at the end of the input)
+fn read_field(input: &str, delimiter: char) -> (Field, usize, &str) {
+    let mut text = String::new();
+    let mut i = 0;
+    let quoted = input.starts_with('"');
+    if quoted {
+        i = 1;
+        loop {
+            match input[i..].find('"') {
+                Some(p) if input[i + p + 1..].starts_with('"') => {
+                    text.push_str(&input[i..i + p + 1]);
+                    i += p + 2;
+                }
+                Some(p) => {
+                    text.push_str(&input[i..i + p]);
+                    i += p + 1;
+                    break;
+                }
+                None => {
+                    text.push_str(&input[i..]);
+                    i = input.len();
+                    break;
+                }
+            }
+        }
+    }
+    //unquoted field, or what follows the closing quote
+    let end = input[i..].find([delimiter, '\n', '\r']).map_or(input.len(), |p| i + p);
+    text.push_str(&input[i..end]);
+    let rest = &input[end..];
+    let separator = if rest.starts_with("\r\n") {
+        "\r\n"
+    } else {
+        rest.chars().next().map_or("", |c| &rest[..c.len_utf8()])
+    };
+    (Field { text, quoted }, end, separator)
+}
+
+//Quoted, with its quotes doubled, if it was or if it must be
+pub(crate) fn write_field(text: &str, quoted: bool, delimiter: char, output: &mut String) {
+    if quoted || text.contains([delimiter, '"', '\n', '\r']) {
+        output.push('"');
+        output.push_str(&text.replace('"', "\"\""));
+        output.push('"');
+    } else {
+        output.push_str(text);
+    }
+}
+
+//Records of the table, each field with the separator that follows it
+fn read_records(input: &str, delimiter: char) -> Vec<Vec<(Field, &str)>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut rest = input;
+    while !rest.is_empty() {
+        let (field, length, separator) = read_field(rest, delimiter);
+        rest = &rest[length + separator.len()..];
+        let end_of_record = separator != delimiter.to_string();
+        record.push((field, separator));
+        if end_of_record {
+            records.push(std::mem::take(&mut record));
+        }
+    }
+    //the input ends with a delimiter: the last field is empty
+    if !record.is_empty() {
+        record.push((Field { text: String::new(), quoted: false }, ""));
+        records.push(record);
+    }
+    records
+}
+
+//Converts the fields of the given columns (all of them if none is given) of a CSV or TSV table.
+//Quoting, delimiters and line endings are kept; a converted field is quoted if it must be. With
+//header, or when columns are given by their header name, the first record is the header and is
+//kept as it is. 8-bit input is read as Windows-1252 and written as UTF-8.
+pub fn convert_csv(input: &[u8], delimiter: char, columns: &Columns, header: bool, conversion: &FontConversion) -> Result<Vec<u8>, String> {
+    let text = match std::str::from_utf8(input) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes_to_low_byte(input),
+    };
+    let conversion = conversion.for_document(&text);
+    let records = read_records(&text, delimiter);
+    let names: Vec<String> = records.first().map(|r| r.iter().map(|(f, _)| f.text.clone()).collect()).unwrap_or_default();
+    let selected = columns.resolve(&names)?;
+    let skip_header = header || columns.uses_header(&names);
+
+    let mut output = String::with_capacity(text.len());
+    for (row, record) in records.into_iter().enumerate() {
+        for (column, (mut field, separator)) in record.into_iter().enumerate() {
+            let convert = !(skip_header && row == 0) && (columns.is_empty() || selected.contains(&column));
+            if convert && !field.text.is_empty() {
+                field.text = conversion.convert(&field.text);
+            }
+            write_field(&field.text, field.quoted, delimiter, &mut output);
+            output.push_str(separator);
+        }
+    }
+    Ok(output.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::fonts::Direction;
+
+    #[test]
+    fn converts_selected_columns() {
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        let csv = "id,Name,City\r\n1,\"\u{f0d8}\u{f0ef}\u{f056}\",\u{f0ef}\r\n2,\"say \"\"\u{f0ef}\"\"\",x\r\n";
+        let converted = convert_csv(csv.as_bytes(), ',', &Columns::parse("Name"), false, &conversion).unwrap();
+        assert_eq!(String::from_utf8(converted).unwrap(), "id,Name,City\r\n1,\"கொ\",\u{f0ef}\r\n2,\"say \"\"க\"\"\",x\r\n");
+
+        let tsv = "\u{f0ef}\t\u{f0ef}\n";
+        let converted = convert_csv(tsv.as_bytes(), '\t', &Columns::parse("2"), false, &conversion).unwrap();
+        assert_eq!(String::from_utf8(converted).unwrap(), "\u{f0ef}\tக\n");
+        assert_eq!(cell_column("AB12"), Some(27));
+    }
+
+    #[test]
+    fn keeps_the_header_row() {
+        let mut conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        conversion.low_byte = true;
+        let csv = "Name,City\n\u{d8}\u{ef}V,\u{ef}\n";
+        for (columns, converted) in [("2", "Name,City\n\u{d8}\u{ef}V,க\n"), ("", "Name,City\nகொ,க\n")] {
+            let output = convert_csv(csv.as_bytes(), ',', &Columns::parse(columns), true, &conversion).unwrap();
+            assert_eq!(String::from_utf8(output).unwrap(), converted);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use crate::fonts::FontConversion;
+use crate::package::{read_parts, rewrite_package};
+use crate::xml::{convert_text, ends_with, rewrite_xml};
+use quick_xml::events::{BytesStart, Event};
+
+//w:rFonts attributes naming a font, and the theme ones that take precedence over them
+const FONT_ATTRIBUTES: [&[u8]; 4] = [b"w:ascii", b"w:hAnsi", b"w:cs", b"w:eastAsia"];
+const THEME_ATTRIBUTES: [&[u8]; 4] = [b"w:asciiTheme", b"w:hAnsiTheme", b"w:cstheme", b"w:eastAsiaTheme"];
+
+//Parts of the package holding the text of the document
+fn is_text_part(name: &str) -> bool {
+    let part = match name.strip_prefix("word/").and_then(|n| n.strip_suffix(".xml")) {
+        Some(part) => part,
+        None => return false,
+    };
+    part == "document" || part == "footnotes" || part == "endnotes" || part == "comments"
+        || part.starts_with("header") || part.starts_with("footer")
+}
+
+//w:rFonts with its fonts replaced by the target font, if one of them is a source font
+fn convert_rfonts(e: &BytesStart, conversion: &FontConversion) -> Result<Option<BytesStart<'static>>, String> {
+    let mut converted = false;
+    let mut attributes = Vec::new();
+    for attribute in e.attributes() {
+        let attribute = attribute.map_err(|e| e.to_string())?;
+        let value = attribute.unescape_value().map_err(|e| e.to_string())?;
+        let key = attribute.key.as_ref();
+        converted |= FONT_ATTRIBUTES.contains(&key) && conversion.is_source_font(&value);
+        attributes.push((key.to_vec(), value.into_owned()));
+    }
+    if !converted {
+        return Ok(None);
+    }
+
+    let mut rfonts = BytesStart::new(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+    for (key, value) in &attributes {
+        if !FONT_ATTRIBUTES.contains(&key.as_slice()) && !THEME_ATTRIBUTES.contains(&key.as_slice()) {
+            rfonts.push_attribute((key.as_slice(), value.as_bytes()));
+        }
+    }
+    for key in FONT_ATTRIBUTES.iter() {
+        rfonts.push_attribute((*key, conversion.target_font().as_bytes()));
+    }
+    Ok(Some(rfonts))
+}
+
+//w:rFonts setting a source font in the properties of a run (rather than of a paragraph mark,
+//or of a tracked change) makes the run converted
+fn mark_run(runs: &mut [bool], path: &[Vec<u8>]) {
+    if ends_with(path, &[b"w:r", b"w:rPr"]) {
+        if let Some(run) = runs.last_mut() {
+            *run = true;
+        }
+    }
+}
+
+//Converts the text of the runs (w:r) whose w:rPr sets a source font in w:rFonts.
+//Runs getting their font from a style are left alone.
+pub fn convert_docx_xml(xml: &str, conversion: &FontConversion) -> Result<String, String> {
+    let mut runs: Vec<bool> = Vec::new(); //for each open run: is it converted
+    rewrite_xml(xml, |event, path| {
+        Ok(match event {
+            Event::Start(e) if e.name().as_ref() == b"w:r" => {
+                runs.push(false);
+                Event::Start(e)
+            }
+            Event::Start(e) if e.name().as_ref() == b"w:rFonts" => match convert_rfonts(&e, conversion)? {
+                Some(rfonts) => {
+                    mark_run(&mut runs, path);
+                    Event::Start(rfonts)
+                }
+                None => Event::Start(e),
+            },
+            Event::Empty(e) if e.name().as_ref() == b"w:rFonts" => match convert_rfonts(&e, conversion)? {
+                Some(rfonts) => {
+                    mark_run(&mut runs, path);
+                    Event::Empty(rfonts)
+                }
+                None => Event::Empty(e),
+            },
+            Event::End(e) => {
+                if e.name().as_ref() == b"w:r" {
+                    runs.pop();
+                }
+                Event::End(e)
+            }
+            Event::Text(e) if runs.last() == Some(&true) && ends_with(path, &[b"w:r", b"w:t"]) => {
+                convert_text(&e, |text| conversion.convert(text))?
+            }
+            event => event,
+        })
+    })
+}
+
+pub fn convert_docx(input: &[u8], conversion: &FontConversion) -> Result<Vec<u8>, String> {
+    let conversion = &conversion.for_document(&read_parts(input, is_text_part)?);
+    rewrite_package(input, |name, contents| {
+        if !is_text_part(name) {
+            return Ok(None);
+        }
+        let xml = std::str::from_utf8(contents).map_err(|e| e.to_string())?;
+        convert_docx_xml(xml, conversion).map(|xml| Some(xml.into_bytes()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::fonts::Direction;
+    use std::io::{Cursor, Read, Write};
+
+    const DOCUMENT: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body><w:p><w:r><w:rPr><w:b/><w:rFonts w:ascii="STMZH" w:hAnsi="STMZH" w:hint="default"/></w:rPr><w:t>&#xF0D8;&#xF0EF;&#xF056;</w:t></w:r><w:r><w:rPr><w:rFonts w:ascii="Arial"/></w:rPr><w:t xml:space="preserve"> A &amp; B</w:t></w:r></w:p></w:body></w:document>"#;
+
+    #[test]
+    fn converts_legacy_runs_only() {
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        let xml = convert_docx_xml(DOCUMENT, &conversion).unwrap();
+        assert!(xml.contains(r#"<w:rPr><w:b/><w:rFonts w:hint="default" w:ascii="Latha" w:hAnsi="Latha" w:cs="Latha" w:eastAsia="Latha"/></w:rPr><w:t>கொ</w:t>"#));
+        assert!(xml.contains(r#"<w:rFonts w:ascii="Arial"/></w:rPr><w:t xml:space="preserve"> A &amp; B</w:t>"#));
+
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Encode);
+        let back = convert_docx_xml(&xml, &conversion).unwrap();
+        assert!(back.contains("w:ascii=\"STMZH\""));
+        assert!(back.contains("<w:t>\u{f0d8}\u{f0ef}\u{f056}</w:t>"));
+    }
+
+    #[test]
+    fn converts_package() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        writer.start_file("[Content_Types].xml", options).unwrap();
+        writer.write_all(b"<Types/>").unwrap();
+        writer.start_file("word/document.xml", options).unwrap();
+        writer.write_all(DOCUMENT.as_bytes()).unwrap();
+        let docx = writer.finish().unwrap().into_inner();
+
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        let converted = convert_docx(&docx, &conversion).unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(converted)).unwrap();
+        assert_eq!(archive.file_names().count(), 2);
+        let mut document = String::new();
+        archive.by_name("word/document.xml").unwrap().read_to_string(&mut document).unwrap();
+        assert!(document.contains("<w:t>கொ</w:t>"));
+    }
+}
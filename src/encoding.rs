@@ -0,0 +1,370 @@
+use crate::{
+    add_separated_conjuncts, conv_marked_special_entity, conv_special_entity, decode_greedy, parse_entity, with_fallbacks,
+    unic_stmzh_map_char_char, unic_stmzh_map_tuple_char, TamilDetailedEntity,
+    AU_LENGTH_MARK, CONSONANTS, NON_RIDING_MARKS, RIDING_MARKS, VOWELS,
+};
+use crate::normalize::{nfd, normalize};
+use crate::low_byte::{bytes_to_low_byte, is_pua, low_byte_to_bytes, low_byte_to_pua, pua_to_low_byte};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+const PULLI: char = '\u{0bcd}';
+
+//Glyphs drawn around the consonant for a mark that doesn't ride on it:
+//MarkType::Preceding has only a prefix, MarkType::Following only a suffix,
+//MarkType::PrecedingAndFollowing both.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct MarkGlyphs {
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub suffix: String,
+}
+
+//A legacy font encoding, as described by a mapping file. Glyphs are strings, since some fonts
+//need several glyphs for one letter. Letters missing from the tables are written as their
+//fallback (ஶ as ஸ, Tamil digits as ASCII digits), or else kept in Unicode.
+//
+//    name = "STMZH"
+//    fonts = ["STMZH"]
+//    [vowels]
+//    "அ" = "\uF0B6"
+//    [consonants]
+//    "க" = "\uF0EF"
+//    [pulli]
+//    "க" = "\uF0C2"
+//    [riding]
+//    "கி" = "\uF0FE"
+//    [marks."ொ"]
+//    prefix = "\uF0D8"
+//    suffix = "\uF056"
+//    [special]
+//    "க்ஷ" = "\uF0B3"
+//    "க்ஷி" = "\uF048"
+//    [symbols]
+//    "௧" = "1"
+//    [pre_reform]
+//    "லை" = "\uF0A1"
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Encoding {
+    pub name: String,
+    #[serde(default)]
+    pub fonts: Vec<String>, //names of the fonts using this encoding
+    #[serde(default)]
+    pub vowels: HashMap<char, String>,
+    #[serde(default)]
+    pub consonants: HashMap<char, String>,
+    #[serde(default)]
+    pub pulli: HashMap<char, String>, //consonant -> glyph of the consonant with pulli
+    #[serde(default)]
+    pub riding: HashMap<String, String>, //consonant and riding mark (I, II, U, UU) -> glyph
+    #[serde(default)]
+    pub marks: HashMap<char, MarkGlyphs>, //non riding mark -> glyphs around the consonant
+    #[serde(default)]
+    pub special: HashMap<String, String>, //sri, ksha, and ksha with a mark when it has its own glyph
+    #[serde(default)]
+    pub symbols: HashMap<char, String>, //digits, numbers, ௐ, signs, and the Tamil Supplement
+    #[serde(default)]
+    pub pre_reform: HashMap<String, String>, //old ligatures of consonant and mark (லை, ணா, றா ...) -> glyph
+    #[serde(default)]
+    pub use_pre_reform: bool, //encode writes the pre_reform glyphs; decode always reads them
+}
+
+impl Encoding {
+    #[cfg(feature = "toml")]
+    pub fn from_toml_str(s: &str) -> Result<Self, String> {
+        toml::from_str(s).map_err(|e| e.to_string())
+    }
+
+    pub fn from_json_str(s: &str) -> Result<Self, String> {
+        serde_json::from_str(s).map_err(|e| e.to_string())
+    }
+
+    //The format is taken from the extension: .toml or .json
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            #[cfg(feature = "toml")]
+            Some("toml") => Self::from_toml_str(&contents),
+            Some("json") => Self::from_json_str(&contents),
+            _ => Err(String::from("expected a .toml or .json mapping file")),
+        }
+        .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    //The tables convert_unic_stmzh uses
+    pub fn stmzh() -> Self {
+        let mut encoding = Encoding {
+            name: String::from("STMZH"),
+            fonts: vec![String::from("STMZH")],
+            ..Default::default()
+        };
+        for (c, s) in unic_stmzh_map_char_char() {
+            if VOWELS.contains(c) {
+                encoding.vowels.insert(c, s.to_string());
+            } else {
+                encoding.consonants.insert(c, s.to_string());
+            }
+        }
+        for ((c, m), s) in unic_stmzh_map_tuple_char() {
+            if m == PULLI {
+                encoding.pulli.insert(c, s.to_string());
+            } else {
+                encoding.riding.insert([c, m].iter().collect(), s.to_string());
+            }
+        }
+        let marks = [
+            ('\u{0bbe}', "", "\u{f056}"), //A nedil
+            ('\u{0bc6}', "\u{f0d8}", ""), //E kuril
+            ('\u{0bc7}', "\u{f0bc}", ""), //E nedil
+            ('\u{0bc8}', "\u{f0e7}", ""), //AI
+            ('\u{0bca}', "\u{f0d8}", "\u{f056}"), //O kuril
+            ('\u{0bcb}', "\u{f0bc}", "\u{f056}"), //O nedil
+            ('\u{0bcc}', "\u{f0d8}", "\u{f065}"), //AU
+            (AU_LENGTH_MARK, "", "\u{f065}"),
+        ];
+        for &(m, prefix, suffix) in marks.iter() {
+            encoding.marks.insert(m, MarkGlyphs { prefix: prefix.to_string(), suffix: suffix.to_string() });
+        }
+        for ustring in &["ஸ்ரீ", "க்ஷ"] {
+            encoding.special.insert(ustring.to_string(), conv_special_entity(ustring).unwrap());
+        }
+        for m in RIDING_MARKS.chars() {
+            if let Some(s) = conv_marked_special_entity("க்ஷ", m) {
+                encoding.special.insert(format!("க்ஷ{}", m), s);
+            }
+        }
+        encoding
+    }
+
+    fn composed(&self, base: &str, mark: char) -> Option<String> {
+        self.marks.get(&mark).map(|glyphs| format!("{}{}{}", glyphs.prefix, base, glyphs.suffix))
+    }
+
+    //Pre-1978 ligature of c and m. ொ and ோ of ணா, னா, றா are their prefix and the old ா ligature.
+    fn pre_reform_glyphs(&self, c: char, m: char) -> Option<String> {
+        if let Some(glyphs) = self.pre_reform.get(&[c, m].iter().collect::<String>()) {
+            return Some(glyphs.clone());
+        }
+        let signs: Vec<char> = nfd(&m.to_string()).chars().collect();
+        match signs[..] {
+            [first, second] => {
+                let prefix = &self.marks.get(&first)?.prefix;
+                self.pre_reform.get(&[c, second].iter().collect::<String>()).map(|glyphs| format!("{}{}", prefix, glyphs))
+            }
+            _ => None,
+        }
+    }
+
+    fn conv_entity(&self, entity: &TamilDetailedEntity, pre_reform: bool) -> Option<String> {
+        use TamilDetailedEntity::*;
+        match *entity {
+            Vowel(c) => self.vowels.get(&c).cloned(),
+            Consonant(c) => self.consonants.get(&c).cloned(),
+            SeparateEntity((c, m)) if m == PULLI => self.pulli.get(&c).cloned(),
+            SeparateEntity((c, m)) => self.riding.get(&[c, m].iter().collect::<String>()).cloned(),
+            ComposedEntity((c, _, m)) => self.pre_reform_glyphs(c, m).filter(|_| pre_reform)
+                .or_else(|| self.consonants.get(&c).and_then(|s| self.composed(s, m))),
+            SpecialEntity(ustring) => self.special.get(ustring).cloned(),
+            MarkedSpecialConsonant((ustring, _, m)) => self.special.get(&format!("{}{}", ustring, m)).cloned()
+                .or_else(|| self.special.get(ustring).and_then(|s| self.composed(s, m))),
+            Mark((_, m)) => self.marks.get(&m).map(|glyphs| format!("{}{}", glyphs.prefix, glyphs.suffix)).filter(|s| !s.is_empty()),
+            Digit(c) | Number(c) | Fraction(c) | Sign(c) => self.symbols.get(&c).cloned(),
+            Other(_) => None,
+        }
+    }
+
+    pub(crate) fn has_glyph(&self, c: char) -> bool {
+        self.vowels.contains_key(&c) || self.consonants.contains_key(&c) || self.symbols.contains_key(&c)
+    }
+
+    //Same walk as convert_unic_stmzh, with this encoding's tables
+    pub fn encode(&self, source: &str) -> String {
+        self.encode_orthography(source, self.use_pre_reform)
+    }
+
+    //encode, with the old ligatures of the pre_reform table when pre_reform is set
+    pub fn encode_orthography(&self, source: &str, pre_reform: bool) -> String {
+        let source = with_fallbacks(&normalize(source), |c| self.has_glyph(c));
+        let mut output = String::with_capacity(source.len());
+        let mut data = source.as_str();
+        while let Ok((rest, entity)) = parse_entity(data) {
+            match self.conv_entity(&entity, pre_reform) {
+                Some(s) => output.push_str(&s),
+                None => output.push_str(&data[..data.len() - rest.len()]), //no glyph: kept in unicode
+            }
+            data = rest;
+        }
+        output
+    }
+
+    //Every glyph sequence encode can produce, with the Unicode text it stands for
+    pub fn decode_map(&self) -> HashMap<String, String> {
+        let mut map = HashMap::new();
+        for (m, glyphs) in &self.marks { //glyphs shared with a letter (ௗ, ள) decode as the letter
+            if !glyphs.prefix.is_empty() || !glyphs.suffix.is_empty() {
+                map.insert(format!("{}{}", glyphs.prefix, glyphs.suffix), m.to_string());
+            }
+        }
+        for (c, s) in self.vowels.iter().chain(self.consonants.iter()).chain(self.symbols.iter()) {
+            map.insert(s.clone(), c.to_string());
+        }
+        for (c, s) in &self.pulli {
+            map.insert(s.clone(), [*c, PULLI].iter().collect());
+        }
+        for (ustring, s) in &self.riding {
+            map.insert(s.clone(), ustring.clone());
+        }
+        for m in NON_RIDING_MARKS.chars() {
+            for (c, s) in &self.consonants {
+                if let Some(composed) = self.composed(s, m) {
+                    map.insert(composed, [*c, m].iter().collect());
+                }
+            }
+            for (ustring, s) in &self.special {
+                let marked = format!("{}{}", ustring, m);
+                if CONSONANTS.chars().any(|c| ustring.ends_with(c)) && !self.special.contains_key(&marked) {
+                    if let Some(composed) = self.composed(s, m) {
+                        map.insert(composed, marked);
+                    }
+                }
+            }
+        }
+        for (ustring, s) in &self.special {
+            map.insert(s.clone(), ustring.clone());
+        }
+        for c in CONSONANTS.chars() {
+            for m in NON_RIDING_MARKS.chars() {
+                if let Some(glyphs) = self.pre_reform_glyphs(c, m) {
+                    map.insert(glyphs, [c, m].iter().collect());
+                }
+            }
+        }
+        add_separated_conjuncts(&mut map);
+        map
+    }
+
+    pub fn decode(&self, source: &str) -> String {
+        decode_greedy(&self.decode_map(), source)
+    }
+
+    //encode, with glyphs U+F0XX written as the Windows-1252 character of byte 0xXX
+    pub fn encode_low_byte(&self, source: &str) -> String {
+        pua_to_low_byte(&self.encode(source))
+    }
+
+    //Reverse of encode_low_byte. Bytes that are no glyph of the encoding are kept.
+    pub fn decode_low_byte(&self, source: &str) -> String {
+        pua_to_low_byte(&self.decode(&low_byte_to_pua(source)))
+    }
+
+    //Legacy 8-bit text: one byte per glyph
+    pub fn encode_bytes(&self, source: &str) -> Vec<u8> {
+        low_byte_to_bytes(&self.encode_low_byte(source))
+    }
+
+    pub fn decode_bytes(&self, source: &[u8]) -> String {
+        self.decode_low_byte(&bytes_to_low_byte(source))
+    }
+
+    //Legacy text either has its glyphs as U+F0XX (saved from Word), or, without any of them,
+    //as 8-bit text read as Windows-1252
+    pub fn decode_auto(&self, source: &str) -> String {
+        if source.chars().any(is_pua) {
+            self.decode(source)
+        } else {
+            self.decode_low_byte(source)
+        }
+    }
+
+    //Legacy text read from a file. 8-bit files are often valid UTF-8 by chance (க்ட is C2 A6,
+    //"¦"), so they are only told from text when they can't be UTF-8, unless low_byte says so.
+    pub fn decode_file(&self, input: &[u8], low_byte: bool) -> String {
+        match std::str::from_utf8(input) {
+            Ok(text) if !low_byte => self.decode_auto(text),
+            _ => self.decode_bytes(input),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{convert_stmzh_unic, convert_unic_stmzh};
+
+    const ALPHABET: &str = "அகர முதல எழுத்தெல்லாம்
+அஆஇஈஉஊஎஏஐஒஓஔஃ ஸ்ரீக்ஷக்ஷ்க்ஷெக்ஷேக்ஷொக்ஷோக்ஷௌக்ஷக்ஷாக்ஷிக்ஷீக்ஷுக்ஷூ
+க்ங்ச் கிஙிசி கீஙீ குஙு கூஙூ காஙா கெஙெ கேஙே கைஙை கொஙொ கோஙோ கௌஙௌ";
+
+    #[test]
+    fn generic_engine_matches_stmzh() {
+        let encoding = Encoding::stmzh();
+        let encoded = encoding.encode(ALPHABET);
+        assert_eq!(encoded, convert_unic_stmzh(ALPHABET));
+        assert_eq!(encoding.decode(&encoded), convert_stmzh_unic(&encoded));
+        assert_eq!(encoding.encode("ஶொ ௐ ௧௰ ா"), convert_unic_stmzh("ஶொ ௐ ௧௰ ா"));
+        let low_byte = encoding.encode_low_byte("கொ 12.");
+        assert_eq!(low_byte, "\u{d8}\u{ef}V 12.");
+        assert_eq!(encoding.decode_low_byte(&low_byte), "கொ 12.");
+        let bytes = encoding.encode_bytes("கொ 12.");
+        assert_eq!(bytes, b"\xd8\xefV 12.");
+        assert_eq!(encoding.decode_bytes(&bytes), "கொ 12.");
+        let bytes = encoding.encode_bytes("க்ட");
+        assert_eq!(bytes, b"\xc2\xa6");
+        assert_eq!(encoding.decode_file(&bytes, true), "க்ட");
+        assert_eq!(encoding.decode_file(b"\xd8\xefV", false), "கொ");
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn stmzh_mapping_file() {
+        let encoding = Encoding::from_toml_str(include_str!("../encodings/stmzh.toml")).unwrap();
+        let encoded = encoding.encode(ALPHABET);
+        assert_eq!(encoded, convert_unic_stmzh(ALPHABET));
+        assert_eq!(encoding.decode(&encoded), ALPHABET);
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn loads_mapping_files() {
+        let toml = Encoding::from_toml_str(r#"
+            name = "Mini"
+            [consonants]
+            "க" = "k"
+            [pulli]
+            "க" = "K"
+            [riding]
+            "கி" = "ki"
+            [marks."ொ"]
+            prefix = "e"
+            suffix = "a"
+            "#).unwrap();
+        assert_eq!(toml.encode("க க் கி கொ ம"), "k K ki eka ம");
+        assert_eq!(toml.decode("k K ki eka ம"), "க க் கி கொ ம");
+
+        let json = Encoding::from_json_str(r#"{"name": "Mini", "vowels": {"அ": "a"}, "marks": {"ா": {"suffix": "A"}}, "consonants": {"ம": "m"}}"#).unwrap();
+        assert_eq!(json.encode("அமா"), "amA");
+        assert!(Encoding::from_json_str(r#"{"name": "Bad", "marks": {"ொ": {"prefix": 1}}}"#).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn pre_reform_glyphs() {
+        use crate::fonts::{Direction, FontConversion};
+        let mut encoding = Encoding::from_toml_str(r#"
+            name = "Old"
+            consonants = { "ல" = "l", "ண" = "N" }
+            marks = { "ை" = { prefix = "ai" }, "ா" = { suffix = "A" }, "ெ" = { prefix = "e" }, "ொ" = { prefix = "e", suffix = "A" } }
+            pre_reform = { "லை" = "L", "ணா" = "Z" }
+            "#).unwrap();
+        assert_eq!(encoding.encode("லை ணா ணொ"), "ail NA eNA");
+        let mut conversion = FontConversion::new(encoding.clone(), Direction::Encode);
+        conversion.pre_reform = true;
+        assert_eq!(conversion.convert("லை ணா ணொ"), "L Z eZ");
+        encoding.use_pre_reform = true;
+        assert_eq!(encoding.encode("லை ணா ணொ"), "L Z eZ");
+        assert_eq!(encoding.decode("L Z eZ ail"), "லை ணா ணொ லை");
+    }
+}
@@ -0,0 +1,228 @@
+use crate::css::{rewrite_style_sheet, FontRules};
+use crate::fonts::FontConversion;
+use crate::html::convert_markup;
+use crate::package::{part_names, put_first_stored, read_part, read_parts, rewrite_package};
+use crate::xml::{attribute, convert_text, ends_with, rewrite_xml};
+use quick_xml::events::Event;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PartKind {
+    Content,
+    Navigation,
+    Ncx,
+    StyleSheet,
+}
+
+//"Text/Chapter%201.xhtml" in "OEBPS/content.opf" -> "OEBPS/Text/Chapter 1.xhtml"
+fn resolve_href(opf: &str, href: &str) -> String {
+    let mut path: Vec<&str> = opf.split('/').collect();
+    path.pop();
+    let href = href.split('#').next().unwrap_or_default();
+    for segment in href.split('/') {
+        match segment {
+            ".." => {
+                path.pop();
+            }
+            "." | "" => (),
+            segment => path.push(segment),
+        }
+    }
+    let path = path.join("/");
+    let mut decoded = Vec::with_capacity(path.len());
+    let bytes = path.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], path.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).unwrap_or(path)
+}
+
+//Parts of the book, from the manifest of its package document (or, without one, from
+//the extensions of the entries)
+fn book_parts(input: &[u8]) -> Result<HashMap<String, PartKind>, String> {
+    let mut parts = HashMap::new();
+    let mut opf = None;
+    if let Some(container) = read_part(input, "META-INF/container.xml")? {
+        rewrite_xml(&container, |event, _| {
+            if let Event::Start(e) | Event::Empty(e) = &event {
+                if e.local_name().as_ref() == b"rootfile" && opf.is_none() {
+                    opf = attribute(e, b"full-path")?;
+                }
+            }
+            Ok(event)
+        })?;
+    }
+    if let Some(opf) = opf {
+        let package = read_part(input, &opf)?.ok_or_else(|| format!("{} not found", opf))?;
+        rewrite_xml(&package, |event, _| {
+            if let Event::Start(e) | Event::Empty(e) = &event {
+                if e.local_name().as_ref() == b"item" {
+                    let media_type = attribute(e, b"media-type")?.unwrap_or_default();
+                    let properties = attribute(e, b"properties")?.unwrap_or_default();
+                    let kind = match media_type.as_str() {
+                        "application/xhtml+xml" | "text/html" if properties.split_whitespace().any(|p| p == "nav") => Some(PartKind::Navigation),
+                        "application/xhtml+xml" | "text/html" => Some(PartKind::Content),
+                        "application/x-dtbncx+xml" => Some(PartKind::Ncx),
+                        "text/css" => Some(PartKind::StyleSheet),
+                        _ => None,
+                    };
+                    if let (Some(kind), Some(href)) = (kind, attribute(e, b"href")?) {
+                        parts.insert(resolve_href(&opf, &href), kind);
+                    }
+                }
+            }
+            Ok(event)
+        })?;
+        return Ok(parts);
+    }
+    for name in part_names(input)? {
+        let extension = name.rsplit('.').next().unwrap_or_default().to_lowercase();
+        let kind = match extension.as_str() {
+            "xhtml" | "html" | "htm" => PartKind::Content,
+            "ncx" => PartKind::Ncx,
+            "css" => PartKind::StyleSheet,
+            _ => continue,
+        };
+        parts.insert(name, kind);
+    }
+    Ok(parts)
+}
+
+//Labels of an NCX table of contents that look like source text
+fn convert_ncx(xml: &str, conversion: &FontConversion) -> Result<String, String> {
+    rewrite_xml(xml, |event, path| {
+        Ok(match event {
+            Event::Text(e) if ends_with(path, &[b"navLabel", b"text"]) || ends_with(path, &[b"docTitle", b"text"]) || ends_with(path, &[b"docAuthor", b"text"]) => {
+                convert_text(&e, |text| if conversion.is_source_text(text) { conversion.convert(text) } else { text.to_string() })?
+            }
+            event => event,
+        })
+    })
+}
+
+//Converts the text of the content documents set in a source font, through their own styles
+//and the style sheets of the book, and rewrites @font-face rules and font-family declarations
+//to the target font. When the book uses a source font, the labels of its navigation documents
+//(nav and NCX) are converted too: those in a source font, and those that look like source text
+//(FontConversion::is_source_text) when no element sets another font. The book is decoded one
+//way throughout, and repackaged with its mimetype entry first and uncompressed.
+pub fn convert_epub(input: &[u8], conversion: &FontConversion) -> Result<Vec<u8>, String> {
+    let parts = book_parts(input)?;
+    let conversion = &conversion.for_document(&read_parts(input, |name| parts.get(name).is_some_and(|kind| *kind != PartKind::StyleSheet))?);
+    let mut rules = FontRules::default();
+    let mut converted: HashMap<String, String> = HashMap::new();
+    let mut uses_source_font = false;
+    for (name, kind) in &parts {
+        if *kind == PartKind::StyleSheet {
+            if let Some(css) = read_part(input, name)? {
+                rules.add_style_sheet(&css);
+                let rewritten = rewrite_style_sheet(&css, conversion);
+                uses_source_font |= rewritten != css;
+                converted.insert(name.clone(), rewritten);
+            }
+        }
+    }
+    for (name, kind) in &parts {
+        if *kind == PartKind::Content {
+            if let Some(xhtml) = read_part(input, name)? {
+                let rewritten = convert_markup(&xhtml, rules.clone(), false, false, conversion);
+                uses_source_font |= rewritten != xhtml;
+                converted.insert(name.clone(), rewritten);
+            }
+        }
+    }
+    for (name, kind) in &parts {
+        let document = match kind {
+            PartKind::Navigation | PartKind::Ncx => read_part(input, name)?,
+            _ => None,
+        };
+        let rewritten = match (kind, document) {
+            (PartKind::Navigation, Some(xhtml)) => convert_markup(&xhtml, rules.clone(), uses_source_font, false, conversion),
+            (PartKind::Ncx, Some(ncx)) if uses_source_font => convert_ncx(&ncx, conversion)?,
+            _ => continue,
+        };
+        converted.insert(name.clone(), rewritten);
+    }
+
+    let book = rewrite_package(input, |name, _| Ok(converted.remove(name).map(String::into_bytes)))?;
+    put_first_stored(&book, "mimetype", b"application/epub+zip")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::fonts::Direction;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+
+    const CONTAINER: &str = r#"<container><rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#;
+    const OPF: &str = r#"<package><manifest><item id="c1" href="Text/chapter%201.xhtml" media-type="application/xhtml+xml"/><item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/><item id="css" href="Styles/book.css" media-type="text/css"/><item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/></manifest></package>"#;
+    const CSS: &str = "@font-face { font-family: STMZH; src: url(../Fonts/stmzh.ttf) }\n.tamil { font-family: STMZH }";
+    const CHAPTER: &str = r#"<html xmlns="http://www.w3.org/1999/xhtml"><body><p class="tamil">&#xF0D8;&#xF0EF;&#xF056;</p><p>&#xF0EF;</p></body></html>"#;
+    const NAV: &str = r#"<html xmlns="http://www.w3.org/1999/xhtml"><body><nav><ol><li><a href="Text/chapter%201.xhtml">LABEL</a></li><li><a href="Text/contents.xhtml">Contents</a></li></ol></nav></body></html>"#;
+    const NCX: &str = r#"<ncx><navMap><navPoint><navLabel><text>LABEL</text></navLabel></navPoint><navPoint><navLabel><text>Contents</text></navLabel></navPoint></navMap></ncx>"#;
+
+    //Entries of a book with its style sheet, chapter, and the label of the chapter in its navigation
+    fn entries(css: &str, chapter: &str, label: &str) -> Vec<(&'static str, String)> {
+        vec![("META-INF/container.xml", CONTAINER.to_string()), ("mimetype", "application/epub+zip".to_string()), ("OEBPS/content.opf", OPF.to_string()),
+            ("OEBPS/Styles/book.css", css.to_string()), ("OEBPS/Text/chapter 1.xhtml", chapter.to_string()),
+            ("OEBPS/nav.xhtml", NAV.replace("LABEL", label)), ("OEBPS/toc.ncx", NCX.replace("LABEL", label))]
+    }
+
+    fn write_book(entries: &[(&str, String)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in entries {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn converts_book() {
+        let entries = entries(CSS, CHAPTER, "&#xF0EF;");
+        let epub = write_book(&entries);
+
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        let book = convert_epub(&epub, &conversion).unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(book.as_slice())).unwrap();
+        let mimetype = archive.by_index(0).unwrap();
+        assert_eq!((mimetype.name(), mimetype.compression()), ("mimetype", zip::CompressionMethod::Stored));
+        drop(mimetype);
+        assert_eq!(archive.len(), entries.len());
+
+        let part = |name| read_part(&book, name).unwrap().unwrap();
+        assert_eq!(part("OEBPS/Styles/book.css"), "@font-face { font-family: Latha; src: local(Latha) }\n.tamil { font-family: Latha }");
+        assert!(part("OEBPS/Text/chapter 1.xhtml").contains("<p class=\"tamil\">கொ</p><p>&#xF0EF;</p>"));
+        assert!(part("OEBPS/nav.xhtml").contains("<a href=\"Text/chapter%201.xhtml\">க</a></li><li><a href=\"Text/contents.xhtml\">Contents</a>"));
+        assert!(part("OEBPS/toc.ncx").contains("<text>க</text></navLabel></navPoint><navPoint><navLabel><text>Contents</text>"));
+    }
+
+    #[test]
+    fn converts_navigation_when_encoding_and_from_8_bit() {
+        let encode = FontConversion::new(Encoding::stmzh(), Direction::Encode);
+        let mut low_byte = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        low_byte.low_byte = true;
+        let books = [
+            (encode, ".tamil { font-family: Latha }", "<p class=\"tamil\">கொ</p>", "க", "\u{f0ef}"),
+            (low_byte, ".tamil { font-family: STMZH }", "<p class=\"tamil\">\u{d8}\u{ef}V</p>", "\u{ef}", "க"),
+        ];
+        for (conversion, css, chapter, label, converted) in &books {
+            let book = convert_epub(&write_book(&entries(css, chapter, label)), conversion).unwrap();
+            let part = |name| read_part(&book, name).unwrap().unwrap();
+            assert!(part("OEBPS/nav.xhtml").contains(&format!("{}</a></li><li><a href=\"Text/contents.xhtml\">Contents</a>", converted)));
+            assert!(part("OEBPS/toc.ncx").contains(&format!("<text>{}</text></navLabel></navPoint><navPoint><navLabel><text>Contents</text>", converted)));
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::encoding::Encoding;
+use crate::low_byte::{is_pua, pua_to_low_byte};
+use crate::normalize::is_tamil;
+
+//Fonts drawing Tamil from Unicode text. Runs set in one of them are converted when encoding.
+pub const UNICODE_TAMIL_FONTS: &[&str] = &[
+    "Latha",
+    "Vijaya",
+    "Nirmala UI",
+    "Noto Sans Tamil",
+    "Noto Serif Tamil",
+    "Lohit Tamil",
+    "Arial Unicode MS",
+];
+
+pub const DEFAULT_UNICODE_FONT: &str = "Latha";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Decode, //legacy font -> Unicode
+    Encode, //Unicode -> legacy font
+}
+
+//How documents mixing fonts are converted: only text set in a source font is converted,
+//and its font is replaced by the target font.
+#[derive(Debug, Clone)]
+pub struct FontConversion {
+    pub encoding: Encoding,
+    pub direction: Direction,
+    pub unicode_font: String,
+    pub low_byte: bool, //legacy text is written with the bytes of the font (0xXX) rather than U+F0XX
+    pub pua: Option<bool>, //decoded text has U+F0XX glyphs (true) or is 8-bit (false); None: told from each text
+    pub pre_reform: bool, //encoded text uses the old ligatures of the encoding's pre_reform table
+}
+
+//"STMZH", 'stmzh', ' Stmzh ' name the same font
+fn same_font(a: &str, b: &str) -> bool {
+    let clean = |s: &str| s.trim().trim_matches(|c| c == '"' || c == '\'').trim().to_lowercase();
+    clean(a) == clean(b)
+}
+
+//Code of the character reference at the start of s, after its &# or \u:
+//"xF0D8;" (hex), "61656;" (decimal), "-3880?" (an RTF escape, a signed 16-bit unit)
+fn escaped_code(s: &str) -> Option<u32> {
+    let (radix, digits) = match s.strip_prefix(['x', 'X']) {
+        Some(hex) => (16, hex),
+        None => (10, s),
+    };
+    let negative = radix == 10 && digits.starts_with('-');
+    let digits = if negative { &digits[1..] } else { digits };
+    let end = digits.find(|c: char| !c.is_digit(radix)).unwrap_or(digits.len());
+    let code = u32::from_str_radix(&digits[..end], radix).ok()?;
+    if negative { 0x10000u32.checked_sub(code) } else { Some(code) }
+}
+
+//Does the document have U+F0XX glyphs, as characters or as escapes (&#xF0D8;, \u-3880)
+fn has_pua_glyphs(document: &str) -> bool {
+    let escaped = |prefix| document.match_indices(prefix)
+        .any(|(i, _)| escaped_code(&document[i + 2..]).and_then(std::char::from_u32).is_some_and(is_pua));
+    document.chars().any(is_pua) || escaped("&#") || escaped("\\u")
+}
+
+impl FontConversion {
+    pub fn new(encoding: Encoding, direction: Direction) -> Self {
+        FontConversion {
+            encoding,
+            direction,
+            unicode_font: DEFAULT_UNICODE_FONT.to_string(),
+            low_byte: false,
+            pua: None,
+            pre_reform: false,
+        }
+    }
+
+    pub fn is_legacy_font(&self, font: &str) -> bool {
+        same_font(font, &self.encoding.name) || self.encoding.fonts.iter().any(|f| same_font(font, f))
+    }
+
+    pub fn is_unicode_font(&self, font: &str) -> bool {
+        same_font(font, &self.unicode_font) || UNICODE_TAMIL_FONTS.iter().any(|f| same_font(font, f))
+    }
+
+    //Is text set in font to be converted
+    pub fn is_source_font(&self, font: &str) -> bool {
+        match self.direction {
+            Direction::Decode => self.is_legacy_font(font),
+            Direction::Encode => self.is_unicode_font(font),
+        }
+    }
+
+    //Font of the converted text
+    pub fn target_font(&self) -> &str {
+        match self.direction {
+            Direction::Decode => &self.unicode_font,
+            Direction::Encode => self.encoding.fonts.first().unwrap_or(&self.encoding.name),
+        }
+    }
+
+    //Can text no font is given for be in the source encoding: Tamil letters when encoding,
+    //U+F0XX glyphs when decoding, or, for 8-bit text, characters beyond ASCII (as most glyphs
+    //of a symbol font are)
+    pub fn is_source_text(&self, text: &str) -> bool {
+        match self.direction {
+            Direction::Encode => text.chars().any(is_tamil),
+            Direction::Decode if self.low_byte || self.pua == Some(false) => !text.is_ascii(),
+            Direction::Decode => text.chars().any(is_pua),
+        }
+    }
+
+    pub fn convert(&self, text: &str) -> String {
+        match (self.direction, self.low_byte) {
+            (Direction::Decode, _) => match self.pua {
+                Some(true) => self.encoding.decode(text),
+                Some(false) => self.encoding.decode_low_byte(text),
+                None => self.encoding.decode_auto(text),
+            },
+            (Direction::Encode, low_byte) => {
+                let encoded = self.encoding.encode_orthography(text, self.pre_reform || self.encoding.use_pre_reform);
+                if low_byte { pua_to_low_byte(&encoded) } else { encoded }
+            }
+        }
+    }
+
+    //This conversion for the pieces of a document: decode_auto tells U+F0XX glyphs from 8-bit
+    //text by looking at the text itself, and a document is decoded one way throughout, not a
+    //different one for each piece. document is its source (markup, xml, rtf) or its text.
+    pub fn for_document(&self, document: &str) -> FontConversion {
+        FontConversion { pua: Some(has_pua_glyphs(document)), ..self.clone() }
+    }
+
+    //convert, for_document
+    pub fn document_converter(&self, document: &str) -> Box<dyn Fn(&str) -> String + '_> {
+        let conversion = self.for_document(document);
+        Box::new(move |s| conversion.convert(s))
+    }
+}
@@ -0,0 +1,323 @@
+use crate::css::{font_family, rewrite_font_families, rewrite_font_family_value, rewrite_style_sheet, FontRules};
+use crate::fonts::FontConversion;
+use crate::low_byte::bytes_to_low_byte;
+use std::ops::Range;
+
+//Elements without content or end tag
+const VOID_ELEMENTS: [&str; 14] = ["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr"];
+//Elements whose content is not markup
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+
+struct Attribute {
+    name: String,
+    value: Option<Range<usize>>, //in the tag
+}
+
+impl Attribute {
+    fn value<'a>(&self, tag: &'a str) -> Option<&'a str> {
+        self.value.clone().map(|range| &tag[range])
+    }
+}
+
+//Attributes of the start tag `<name ...>`
+fn parse_attributes(tag: &str, name_end: usize) -> Vec<Attribute> {
+    let bytes = tag.as_bytes();
+    let end = if tag.ends_with('>') { tag.len() - 1 } else { tag.len() }; //the closing >, if any
+    let mut attributes = Vec::new();
+    let mut i = name_end;
+    while i < end {
+        if bytes[i].is_ascii_whitespace() || bytes[i] == b'/' {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < end && !bytes[i].is_ascii_whitespace() && bytes[i] != b'=' && bytes[i] != b'/' {
+            i += 1;
+        }
+        let name = tag[start..i].to_ascii_lowercase();
+        while i < end && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i >= end || bytes[i] != b'=' {
+            attributes.push(Attribute { name, value: None });
+            continue;
+        }
+        i += 1;
+        while i < end && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let value = if i < end && (bytes[i] == b'"' || bytes[i] == b'\'') {
+            let quote = bytes[i];
+            let value_end = tag[i + 1..end].bytes().position(|b| b == quote).map_or(end, |p| i + 1 + p);
+            let value = i + 1..value_end;
+            i = value_end + 1;
+            value
+        } else {
+            let value_start = i;
+            while i < end && !bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            value_start..i
+        };
+        attributes.push(Attribute { name, value: Some(value) });
+    }
+    attributes
+}
+
+//Length of the tag starting at the beginning of html (up to its >, quotes aware)
+fn tag_length(html: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in html.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return i + 1,
+            _ => (),
+        }
+    }
+    html.len()
+}
+
+fn unescape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(end) if end <= 10 => end,
+            _ => {
+                output.push('&');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ => entity.strip_prefix('#').and_then(|number| {
+                match number.strip_prefix('x').or_else(|| number.strip_prefix('X')) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => number.parse().ok(),
+                }
+            }).and_then(std::char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                output.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+struct HtmlConverter<'a> {
+    conversion: &'a FontConversion,
+    rules: FontRules,
+    elements: Vec<(String, Option<bool>)>, //open elements, and whether the font they set is a source font
+    guess_text: bool, //text in no font is converted when it looks like source text
+    to_utf8: bool, //the input was 8-bit: charset declarations are changed to utf-8
+    output: String,
+}
+
+impl HtmlConverter<'_> {
+    fn text(&mut self, text: &str) {
+        let unescaped = unescape(text);
+        let convert = match self.elements.iter().rev().find_map(|(_, font)| *font) {
+            Some(source_font) => source_font,
+            None => self.guess_text && self.conversion.is_source_text(&unescaped),
+        };
+        if convert && !text.trim().is_empty() {
+            let converted = self.conversion.convert(&unescaped);
+            self.output.push_str(&escape(&converted));
+        } else {
+            self.output.push_str(text);
+        }
+    }
+
+    fn end_tag(&mut self, tag: &str) {
+        let name = tag[2..].trim_end_matches('>').trim().to_ascii_lowercase();
+        if let Some(position) = self.elements.iter().rposition(|(n, _)| *n == name) {
+            self.elements.truncate(position);
+        }
+        self.output.push_str(tag);
+    }
+
+    //Writes the start tag with its font declarations rewritten, and returns the element name
+    fn start_tag(&mut self, tag: &str) -> String {
+        let name_end = tag[1..].find(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>').map_or(tag.len(), |p| p + 1);
+        let name = tag[1..name_end].to_ascii_lowercase();
+        let attributes = parse_attributes(tag, name_end);
+        let value = |key: &str| attributes.iter().find(|a| a.name == key).and_then(|a| a.value(tag));
+
+        let family = value("style").and_then(font_family)
+            .or_else(|| if name == "font" { value("face").map(str::to_string) } else { None })
+            .or_else(|| self.rules.font_family(&name, value("id"), value("class")).cloned());
+        let font = family.map(|family| {
+            crate::css::font_families(&family).first().is_some_and(|f| self.conversion.is_source_font(f))
+        });
+
+        let mut replacements: Vec<(Range<usize>, String)> = Vec::new();
+        for attribute in &attributes {
+            let (range, value) = match (attribute.value.clone(), attribute.value(tag)) {
+                (Some(range), Some(value)) => (range, value),
+                _ => continue,
+            };
+            let rewritten = match attribute.name.as_str() {
+                "style" => Some(rewrite_font_families(value, self.conversion)).filter(|v| v != value),
+                "face" if name == "font" => rewrite_font_family_value(value, self.conversion),
+                "charset" if name == "meta" && self.to_utf8 => Some("utf-8".to_string()),
+                "content" if name == "meta" && self.to_utf8 && value.to_ascii_lowercase().contains("charset=") => {
+                    let position = value.to_ascii_lowercase().find("charset=").unwrap_or(0);
+                    Some(format!("{}charset=utf-8", &value[..position]))
+                }
+                _ => None,
+            };
+            if let Some(rewritten) = rewritten {
+                let rewritten = match tag.as_bytes()[range.start - 1] {
+                    b'\'' => rewritten.replace('\'', "&#39;"),
+                    _ => rewritten.replace('"', "&quot;"),
+                };
+                replacements.push((range, rewritten));
+            }
+        }
+        let mut last = 0;
+        for (range, value) in replacements {
+            self.output.push_str(&tag[last..range.start]);
+            self.output.push_str(&value);
+            last = range.end;
+        }
+        self.output.push_str(&tag[last..]);
+
+        if !VOID_ELEMENTS.contains(&name.as_str()) && !tag.ends_with("/>") {
+            self.elements.push((name.clone(), font));
+        }
+        name
+    }
+
+    //Contents of a script or style element up to its end tag; style sheets are read for the
+    //fonts they give and rewritten
+    fn raw_text(&mut self, name: &str, html: &str) -> usize {
+        let end = html.to_ascii_lowercase().find(&format!("</{}", name)).unwrap_or(html.len());
+        let text = &html[..end];
+        if name == "style" {
+            self.rules.add_style_sheet(text);
+            self.output.push_str(&rewrite_style_sheet(text, self.conversion));
+        } else {
+            self.output.push_str(text);
+        }
+        end
+    }
+
+    fn convert(&mut self, html: &str) {
+        let mut rest = html;
+        while !rest.is_empty() {
+            let next = rest.as_bytes().get(1).copied().unwrap_or(0);
+            let length = if rest.starts_with("<!--") {
+                let length = rest.find("-->").map_or(rest.len(), |p| p + 3);
+                self.output.push_str(&rest[..length]);
+                length
+            } else if rest.starts_with('<') && (next == b'!' || next == b'?') {
+                let length = tag_length(rest);
+                self.output.push_str(&rest[..length]);
+                length
+            } else if rest.starts_with("</") && rest.as_bytes().get(2).is_some_and(u8::is_ascii_alphabetic) {
+                let length = tag_length(rest);
+                self.end_tag(&rest[..length]);
+                length
+            } else if rest.starts_with('<') && next.is_ascii_alphabetic() {
+                let length = tag_length(rest);
+                let name = self.start_tag(&rest[..length]);
+                if RAW_TEXT_ELEMENTS.contains(&name.as_str()) && !rest[..length].ends_with("/>") {
+                    length + self.raw_text(&name, &rest[length..])
+                } else {
+                    length
+                }
+            } else {
+                let first = rest.chars().next().map_or(1, char::len_utf8);
+                let length = rest[first..].find('<').map_or(rest.len(), |p| p + first);
+                self.text(&rest[..length]);
+                length
+            };
+            rest = &rest[length..];
+        }
+    }
+}
+
+//Converts the text whose font (from <font face>, style attributes and the simple rules of the
+//style sheets) is a source font. Font declarations are rewritten to the target font; markup,
+//scripts and everything else are kept as is. 8-bit input is read as Windows-1252 and written
+//as UTF-8.
+pub fn convert_html(input: &[u8], conversion: &FontConversion) -> Result<Vec<u8>, String> {
+    let (html, to_utf8) = match std::str::from_utf8(input) {
+        Ok(html) => (html.to_string(), false),
+        Err(_) => (bytes_to_low_byte(input), true),
+    };
+    Ok(convert_markup(&html, FontRules::default(), false, to_utf8, &conversion.for_document(&html)).into_bytes())
+}
+
+//Same for a document styled by rules from outside it (the style sheets of a book). With
+//guess_text, text no element sets a font for is converted too when it looks like source text.
+pub(crate) fn convert_markup(html: &str, rules: FontRules, guess_text: bool, to_utf8: bool, conversion: &FontConversion) -> String {
+    let elements = vec![(String::new(), None)];
+    let mut converter = HtmlConverter { conversion, rules, elements, guess_text, to_utf8, output: String::with_capacity(html.len()) };
+    converter.convert(html);
+    converter.output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::fonts::Direction;
+
+    #[test]
+    fn converts_text_in_legacy_fonts() {
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        let html = "<html><head><style>.t { font-family: STMZH }</style><script>var s = \"<p>\u{f0ef}\";</script></head><body>\
+            <p><font face=\"STMZH\">\u{f0d8}\u{f0ef}\u{f056} &amp;</font> \u{f0ef}</p>\
+            <p class=\"t\">&#xF0EF;<br><span style=\"font-family: Arial\">A &lt; B</span></p>\
+            <div style='font-family: \"STMZH\", serif'><!-- \u{f0ef} -->\u{f0ef}</div></body></html>";
+        let converted = String::from_utf8(convert_html(html.as_bytes(), &conversion).unwrap()).unwrap();
+        assert_eq!(converted, "<html><head><style>.t { font-family: Latha }</style><script>var s = \"<p>\u{f0ef}\";</script></head><body>\
+            <p><font face=\"Latha\">கொ &amp;</font> \u{f0ef}</p>\
+            <p class=\"t\">க<br><span style=\"font-family: Arial\">A &lt; B</span></p>\
+            <div style='font-family: Latha, serif'><!-- \u{f0ef} -->க</div></body></html>");
+
+        let encode = FontConversion::new(Encoding::stmzh(), Direction::Encode);
+        let back = String::from_utf8(convert_html(converted.as_bytes(), &encode).unwrap()).unwrap();
+        assert!(back.contains("<font face=\"STMZH\">\u{f0d8}\u{f0ef}\u{f056} &amp;</font>"));
+    }
+
+    #[test]
+    fn keeps_unterminated_and_non_ascii_tags() {
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        for html in ["<p class=க", "<p title=க", "<p title='தமிழ்", "<p title=\"கொ\" class=அ>\u{f0ef}</p>"] {
+            let converted = String::from_utf8(convert_html(html.as_bytes(), &conversion).unwrap()).unwrap();
+            assert_eq!(converted, html);
+        }
+    }
+
+    #[test]
+    fn decodes_the_page_one_way() {
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        let html = "<p><font face=\"STMZH\">Hi</font> <font face=\"STMZH\">\u{f0ef}</font></p>";
+        let converted = String::from_utf8(convert_html(html.as_bytes(), &conversion).unwrap()).unwrap();
+        assert_eq!(converted, "<p><font face=\"Latha\">Hi</font> <font face=\"Latha\">க</font></p>");
+    }
+}
@@ -9,12 +9,46 @@ use gloo::{events::EventListener};
 // }
 use nom::{
     branch::alt,
-    character::complete::one_of,
+    character::complete::{anychar, char, one_of},
     bytes::complete::tag,
+    combinator::{opt, value, verify},
     IResult,
 };
 use std::collections::HashMap;
 
+pub mod chart;
+pub mod css;
+pub mod csv;
+#[cfg(feature = "documents")]
+pub mod docx;
+pub mod encoding;
+#[cfg(feature = "documents")]
+pub mod epub;
+pub mod fonts;
+pub mod html;
+pub mod localization;
+pub mod low_byte;
+pub mod markup;
+pub mod normalize;
+#[cfg(feature = "documents")]
+pub mod odt;
+#[cfg(feature = "documents")]
+pub mod pdf;
+pub mod rtf;
+pub mod subtitles;
+pub mod supplement;
+#[cfg(feature = "documents")]
+pub mod svg;
+pub mod syllable;
+#[cfg(feature = "documents")]
+mod package;
+pub mod repair;
+pub mod verify;
+#[cfg(feature = "documents")]
+pub mod xlsx;
+#[cfg(feature = "documents")]
+mod xml;
+
 #[derive(Debug)]
 pub enum TamilDetailedEntity<'a>{
     Vowel(char),
@@ -23,7 +57,11 @@ pub enum TamilDetailedEntity<'a>{
     SeparateEntity((char, char)),
     ComposedEntity((char, MarkType, char)),
     SpecialEntity(&'a str), //sri, ksha ...
-    Mark((MarkType, char)),
+    Mark((MarkType, char)), //a sign with no consonant to mark
+    Digit(char), //௦-௯
+    Number(char), //௰ ௱ ௲
+    Fraction(char), //Tamil Supplement fractions
+    Sign(char), //ௐ, calendrical and accounting signs, and the signs of the Tamil Supplement
     Other(char),
     // RidingMark(char),
     // PrecedingMark(char),
@@ -39,43 +77,128 @@ pub enum MarkType {
     PrecedingAndFollowing,
 }
 
+impl TamilDetailedEntity<'_> {
+    //Name of the variant, used when reporting on entities (e.g. round-trip verification)
+    pub fn kind(&self) -> &'static str {
+        use TamilDetailedEntity::*;
+        match self {
+            Vowel(_) => "Vowel",
+            Consonant(_) => "Consonant",
+            MarkedSpecialConsonant(_) => "MarkedSpecialConsonant",
+            SeparateEntity(_) => "SeparateEntity",
+            ComposedEntity(_) => "ComposedEntity",
+            SpecialEntity(_) => "SpecialEntity",
+            Mark(_) => "Mark",
+            Digit(_) => "Digit",
+            Number(_) => "Number",
+            Fraction(_) => "Fraction",
+            Sign(_) => "Sign",
+            Other(_) => "Other",
+        }
+    }
+}
+
+pub const VOWELS: &str = "அஆஇஈஉஊஎஏஐஒஓஔஃ";
+pub const CONSONANTS: &str = "கஙசஞடணதநபமயரலவழளறனஷஜஸஹஶ";
+//pulli, I kuril, I nedil, U kuril, U nedil
+pub const RIDING_MARKS: &str = "\u{0bcd}\u{0bbf}\u{0bc0}\u{0bc1}\u{0bc2}";
+//A nedil, E kuril, E nedil, AI, O kuril, O nedil, AU
+pub const NON_RIDING_MARKS: &str = "\u{0bbe}\u{0bc6}\u{0bc7}\u{0bc8}\u{0bca}\u{0bcb}\u{0bcc}";
+//Vowels and the signs they take after a consonant (அ has none): the riding marks but pulli,
+//and the non riding marks
+pub const VOWEL_SIGNS: [(char, char); 11] = [
+    ('ஆ', '\u{0bbe}'),
+    ('இ', '\u{0bbf}'),
+    ('ஈ', '\u{0bc0}'),
+    ('உ', '\u{0bc1}'),
+    ('ஊ', '\u{0bc2}'),
+    ('எ', '\u{0bc6}'),
+    ('ஏ', '\u{0bc7}'),
+    ('ஐ', '\u{0bc8}'),
+    ('ஒ', '\u{0bca}'),
+    ('ஓ', '\u{0bcb}'),
+    ('ஔ', '\u{0bcc}'),
+];
+//AU length mark
+pub const AU_LENGTH_MARK: char = '\u{0bd7}';
+//Anusvara, only found in old texts
+pub const ANUSVARA: char = '\u{0b82}';
+pub const DIGITS: &str = "௦௧௨௩௪௫௬௭௮௯";
+//Ten, hundred, thousand
+pub const NUMBERS: &str = "௰௱௲";
+//Om, day, month, year, debit, credit, as above, rupee, number
+pub const SIGNS: &str = "ௐ௳௴௵௶௷௸௹௺";
+
+//Text written instead of a letter the legacy fonts have no glyph for: ஶ as ஸ, ௐ spelt ஓம்,
+//the Tamil digits as ASCII digits (both are positional), and the Tamil Supplement fractions
+//and measures as their decomposition. The numbers ௰ ௱ ௲ and the other signs have no
+//equivalent and are kept in Unicode.
+pub fn fallback(c: char) -> Option<String> {
+    match c {
+        'ஶ' => Some(String::from("ஸ")),
+        'ௐ' => Some(String::from("ஓம்")),
+        '௦'..='௯' => char::from_digit(c as u32 - '௦' as u32, 10).map(String::from),
+        _ => supplement::decomposition(c).map(String::from),
+    }
+}
+
+//source with the fallbacks of the letters has_glyph says the font has no glyph for. A fraction
+//after a digit is set apart by a space: ௨ and ¼ (U+11FD0) give "2 1/4", not "21/4".
+pub(crate) fn with_fallbacks<F: Fn(char) -> bool>(source: &str, has_glyph: F) -> String {
+    let mut output = String::with_capacity(source.len());
+    for c in source.chars() {
+        match fallback(c) {
+            Some(fallback) if !has_glyph(c) => {
+                if supplement::is_fraction(c) && output.ends_with(|d: char| d.is_ascii_digit() || DIGITS.contains(d)) {
+                    output.push(' ');
+                }
+                output.push_str(&fallback);
+            }
+            _ => output.push(c),
+        }
+    }
+    output
+}
+
 
+//With ZWJ after the pulli the conjunct is asked for explicitly (ஸ்‍ரீ, க்‍ஷ). With ZWNJ
+//(ஸ்‌ரீ, க்‌ஷ) the letters stay separate, and these don't match.
 fn parse_sri(i: &str) -> IResult<&str, &str> {
-    tag("ஸ்ரீ")(i)
+    alt((tag("ஸ்ரீ"), value("ஸ்ரீ", tag("ஸ்\u{200d}ரீ"))))(i)
 }
 
 fn parse_ksha(i: &str) -> IResult<&str, &str> {
-    tag("க்ஷ")(i)
+    alt((tag("க்ஷ"), value("க்ஷ", tag("க்\u{200d}ஷ"))))(i)
 }
 
-fn parse_special_entity(i: &str) -> IResult<&str, TamilDetailedEntity> {
+fn parse_special_entity(i: &str) -> IResult<&str, TamilDetailedEntity<'_>> {
     let (i, entity) =  alt((parse_sri, parse_ksha))(i)?;
     Ok((i, TamilDetailedEntity::SpecialEntity(entity)))
 }
 
-fn parse_marked_special_consonant(i: &str) -> IResult<&str, TamilDetailedEntity> {
+fn parse_marked_special_consonant(i: &str) -> IResult<&str, TamilDetailedEntity<'_>> {
     let (i, entity) = parse_ksha(i)?;
     let (i, (mark_type, mark)) = parse_mark(i)?;
     Ok((i, TamilDetailedEntity::MarkedSpecialConsonant((entity, mark_type, mark))))
 }
 
-fn parse_vowel(i: &str) -> IResult<&str, TamilDetailedEntity> {
-    let (i, entity) = one_of("அஆஇஈஉஊஎஏஐஒஓஔஃ")(i)?;
+fn parse_vowel(i: &str) -> IResult<&str, TamilDetailedEntity<'_>> {
+    let (i, entity) = one_of(VOWELS)(i)?;
     Ok((i, TamilDetailedEntity::Vowel(entity)))
 }
 
 fn parse_consonant_helper(i: &str) -> IResult<&str, char> {
-    one_of("கஙசஞடணதநபமயரலவழளறனஷஜஸஹ")(i)
+    one_of(CONSONANTS)(i)
 }
 
-fn parse_consonant(i: &str) -> IResult<&str, TamilDetailedEntity> {
+fn parse_consonant(i: &str) -> IResult<&str, TamilDetailedEntity<'_>> {
     let (i, entity) = parse_consonant_helper(i)?;
     Ok((i, TamilDetailedEntity::Consonant(entity)))
 }
 
 //Aka Combining Mark in Unicode: pulli_ta (A kuril), I kuril, I nedil, U kuril, U nedil
 fn parse_riding_mark(i: &str) -> IResult<&str, (MarkType, char)> {
-    let (i, entity) = one_of("\u{0bcd}\u{0bbf}\u{0bc0}\u{0bc1}\u{0bc2}")(i)?;
+    let (i, entity) = one_of(RIDING_MARKS)(i)?;
     Ok((i, (MarkType::Riding, entity)))
 }
 
@@ -113,33 +236,55 @@ fn parse_mark(i: &str) -> IResult<&str, (MarkType, char)> {
     ))(i)
 }
 
-fn parse_other(i: &str) -> IResult<&str, TamilDetailedEntity> {
-    let (i, entity) = nom::character::complete::anychar(i)?;
+//ௗ and ஂ only ever follow another letter
+fn parse_lone_mark(i: &str) -> IResult<&str, TamilDetailedEntity<'_>> {
+    let (i, mark) = alt((
+        parse_mark,
+        |i| char(AU_LENGTH_MARK)(i).map(|(i, m)| (i, (MarkType::Following, m))),
+        |i| char(ANUSVARA)(i).map(|(i, m)| (i, (MarkType::Riding, m))),
+    ))(i)?;
+    Ok((i, TamilDetailedEntity::Mark(mark)))
+}
+
+fn parse_number(i: &str) -> IResult<&str, TamilDetailedEntity<'_>> {
+    alt((
+        |i| one_of(DIGITS)(i).map(|(i, c)| (i, TamilDetailedEntity::Digit(c))),
+        |i| one_of(NUMBERS)(i).map(|(i, c)| (i, TamilDetailedEntity::Number(c))),
+        |i| one_of(SIGNS)(i).map(|(i, c)| (i, TamilDetailedEntity::Sign(c))),
+        |i| verify(anychar, |c| supplement::is_fraction(*c))(i).map(|(i, c)| (i, TamilDetailedEntity::Fraction(c))),
+        |i| verify(anychar, |c| supplement::is_sign(*c))(i).map(|(i, c)| (i, TamilDetailedEntity::Sign(c))),
+    ))(i)
+}
+
+fn parse_other(i: &str) -> IResult<&str, TamilDetailedEntity<'_>> {
+    let (i, entity) = anychar(i)?;
     Ok((i, TamilDetailedEntity::Other(entity)))
     
 }
 
-fn parse_not_markable(i: &str) -> IResult<&str, TamilDetailedEntity> {
+fn parse_not_markable(i: &str) -> IResult<&str, TamilDetailedEntity<'_>> {
     alt((
         parse_special_entity,
         parse_vowel,
     ))(i)
 }
 
-pub fn parse_separate_entity(i: &str) -> IResult<&str, TamilDetailedEntity> {
+//A ZWNJ after a pulli, keeping the consonant apart from the next one, belongs to it
+pub fn parse_separate_entity(i: &str) -> IResult<&str, TamilDetailedEntity<'_>> {
     let (i, consonant) = parse_consonant_helper(i)?;
     let (i, (_, riding_mark)) = parse_riding_mark(i)?;
+    let (i, _) = if riding_mark == '\u{0bcd}' { opt(char('\u{200c}'))(i)? } else { (i, None) };
     Ok((i, TamilDetailedEntity::SeparateEntity((consonant, riding_mark))))
 }
 
-pub fn parse_composed_entity(i: &str) -> IResult<&str, TamilDetailedEntity> {
+pub fn parse_composed_entity(i: &str) -> IResult<&str, TamilDetailedEntity<'_>> {
     let (i, consonant) = parse_consonant_helper(i)?;
     let (i, (mark_type, non_riding_mark)) = parse_non_riding_mark(i)?;
     Ok((i, TamilDetailedEntity::ComposedEntity((consonant, mark_type, non_riding_mark))))
 
 }
 
-pub fn parse_entity(i: &str) -> IResult<&str, TamilDetailedEntity> {
+pub fn parse_entity(i: &str) -> IResult<&str, TamilDetailedEntity<'_>> {
     alt((
         parse_marked_special_consonant,
         parse_special_entity,
@@ -147,6 +292,8 @@ pub fn parse_entity(i: &str) -> IResult<&str, TamilDetailedEntity> {
         parse_composed_entity,
         parse_not_markable,
         parse_consonant,
+        parse_lone_mark,
+        parse_number,
         parse_other,
     ))(i)
 }
@@ -154,10 +301,50 @@ pub fn parse_entity(i: &str) -> IResult<&str, TamilDetailedEntity> {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn stmzh_round_trip() {
+        let source = "அகர முதல எழுத்தெல்லாம் ஆதி பகவன் முதற்றே உலகு";
+        assert_eq!(convert_stmzh_unic(&convert_unic_stmzh(source)), source);
+        let source = "ஸ்ரீ க்ஷ க்ஷ் க்ஷெ க்ஷோ க்ஷௌ க்ஷா கொ கோ கௌ கை";
+        assert_eq!(convert_stmzh_unic(&convert_unic_stmzh(source)), source);
+    }
+
+    #[test]
+    fn joiners_in_conjuncts() {
+        assert_eq!(convert_unic_stmzh("க்\u{200d}ஷ ஸ்\u{200d}ரீ"), convert_unic_stmzh("க்ஷ ஸ்ரீ"));
+        let separate = "க்\u{200c}ஷ க்\u{200c}ஷா ஸ்\u{200c}ரீ";
+        let encoded = convert_unic_stmzh(separate);
+        assert!(!encoded.contains(['\u{200c}', '\u{f0b3}', '\u{f070}']), "{:?}", encoded);
+        assert_eq!(convert_stmzh_unic(&encoded), separate);
+    }
+
+    #[test]
+    fn tamil_block() {
+        let mut data = "ஶௐ௧௰௳ௗ";
+        let mut kinds = Vec::new();
+        while let Ok((rest, entity)) = parse_entity(data) {
+            kinds.push(entity.kind());
+            data = rest;
+        }
+        assert_eq!(kinds, ["Consonant", "Sign", "Digit", "Number", "Sign", "Mark"]);
+        let encoded = convert_unic_stmzh("ஶொ ௐ ௨௦௨௬ ௰");
+        assert_eq!(encoded, convert_unic_stmzh("ஸொ ஓம் 2026 ௰"));
+        assert!(encoded.ends_with('௰'));
+        assert_eq!(convert_stmzh_unic(&convert_unic_stmzh("ா ெ ௗ")), "ா ெ ள");
+
+        let mut signs: Vec<char> = VOWEL_SIGNS.iter().map(|&(v, s)| { assert!(VOWELS.contains(v)); s }).collect();
+        let mut marks: Vec<char> = RIDING_MARKS.chars().skip(1).chain(NON_RIDING_MARKS.chars()).collect();
+        signs.sort();
+        marks.sort();
+        assert_eq!(signs, marks);
+    }
 }
 
 fn conv_special_entity(unicode_string: &str) -> Option<String> {
@@ -189,6 +376,23 @@ fn conv_marked_special_entity(unicode_string: &str, mark: char ) -> Option<Strin
     None
 }
 
+//A vowel sign with no consonant before it: its glyphs alone. ௗ is drawn as ள, and decodes as ள.
+fn conv_lone_mark(umark: char) -> Option<&'static str> {
+    match umark {
+        '\u{0bbe}' => Some("\u{f056}"),
+        '\u{0bc6}' => Some("\u{f0d8}"),
+        '\u{0bc7}' => Some("\u{f0bc}"),
+        '\u{0bc8}' => Some("\u{f0e7}"),
+        '\u{0bca}' => Some("\u{f0d8}\u{f056}"),
+        '\u{0bcb}' => Some("\u{f0bc}\u{f056}"),
+        '\u{0bcc}' => Some("\u{f0d8}\u{f065}"),
+        AU_LENGTH_MARK => Some("\u{f065}"),
+        _ => None,
+    }
+}
+
+//Modern orthography only: the code points of STMZH's pre-1978 ligatures are not known
+//(mapping files give theirs in Encoding::pre_reform, for decoding)
 fn conv_composed_entity(stmzhchar: char, umark: char) -> String {
     let mut res = String::with_capacity(4);
     match umark {
@@ -229,166 +433,177 @@ fn conv_composed_entity(stmzhchar: char, umark: char) -> String {
 }
 
 
-#[wasm_bindgen]
-pub fn convert_unic_stmzh(source: &str) -> String {
-    let mut UNIC_STMZH_MAP_CHAR_CHAR = HashMap::new();
+//Vowels and consonants: one Unicode character, one STMZH glyph
+pub fn unic_stmzh_map_char_char() -> HashMap<char, char> {
+    let mut map = HashMap::new();
     //Vowels
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('அ', '\u{f0b6}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ஆ','\u{f067}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('இ','\u{f0d6}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ஈ','\u{f07e}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('உ','\u{f063}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ஊ','\u{f0bb}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('எ','\u{f0a8}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ஏ','\u{f0b0}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ஐ','\u{f06e}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ஒ','\u{f0ce}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ஓ','\u{f07b}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ஔ','\u{f0c1}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ஃ','\u{f0e0}');
+    map.insert('அ', '\u{f0b6}');
+    map.insert('ஆ','\u{f067}');
+    map.insert('இ','\u{f0d6}');
+    map.insert('ஈ','\u{f07e}');
+    map.insert('உ','\u{f063}');
+    map.insert('ஊ','\u{f0bb}');
+    map.insert('எ','\u{f0a8}');
+    map.insert('ஏ','\u{f0b0}');
+    map.insert('ஐ','\u{f06e}');
+    map.insert('ஒ','\u{f0ce}');
+    map.insert('ஓ','\u{f07b}');
+    map.insert('ஔ','\u{f0c1}');
+    map.insert('ஃ','\u{f0e0}');
 
 
     //Consonants
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('க', '\u{f0ef}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ங', '\u{f0f4}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ச', '\u{f0c4}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ஞ', '\u{f051}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ட', '\u{f0a6}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ண', '\u{f0f0}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('த', '\u{f03e}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ந', '\u{f0e5}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ப', '\u{f0c3}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ம', '\u{f05c}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ய', '\u{f042}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ர', '\u{f0ab}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ல', '\u{f0e9}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('வ', '\u{f06b}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ழ', '\u{f077}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ள', '\u{f065}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ற', '\u{f0c5}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ன', '\u{f0aa}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ஷ', '\u{f0ad}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ஜ', '\u{f0db}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ஸ', '\u{f076}');
-    UNIC_STMZH_MAP_CHAR_CHAR.insert('ஹ', '\u{f0c7}');
-
-    let mut UNIC_STMZH_MAP_TUPLE_CHAR = HashMap::new();
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('க','\u{0bcd}'), '\u{f0c2}');
+    map.insert('க', '\u{f0ef}');
+    map.insert('ங', '\u{f0f4}');
+    map.insert('ச', '\u{f0c4}');
+    map.insert('ஞ', '\u{f051}');
+    map.insert('ட', '\u{f0a6}');
+    map.insert('ண', '\u{f0f0}');
+    map.insert('த', '\u{f03e}');
+    map.insert('ந', '\u{f0e5}');
+    map.insert('ப', '\u{f0c3}');
+    map.insert('ம', '\u{f05c}');
+    map.insert('ய', '\u{f042}');
+    map.insert('ர', '\u{f0ab}');
+    map.insert('ல', '\u{f0e9}');
+    map.insert('வ', '\u{f06b}');
+    map.insert('ழ', '\u{f077}');
+    map.insert('ள', '\u{f065}');
+    map.insert('ற', '\u{f0c5}');
+    map.insert('ன', '\u{f0aa}');
+    map.insert('ஷ', '\u{f0ad}');
+    map.insert('ஜ', '\u{f0db}');
+    map.insert('ஸ', '\u{f076}');
+    map.insert('ஹ', '\u{f0c7}');
+    map
+}
+
+//Consonant with a riding mark (pulli, I kuril, I nedil, U kuril, U nedil): one STMZH glyph
+pub fn unic_stmzh_map_tuple_char() -> HashMap<(char, char), char> {
+    let mut map = HashMap::new();
 
     //pulli
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('க','\u{0bcd}'), '\u{f0c2}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ங','\u{0bcd}'), '\u{f0ba}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ச','\u{0bcd}'), '\u{f0df}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஞ','\u{0bcd}'), '\u{f0de}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ட','\u{0bcd}'), '\u{f0e2}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ண','\u{0bcd}'), '\u{f0f5}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('த','\u{0bcd}'), '\u{f0dd}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ந','\u{0bcd}'), '\u{f0cd}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ப','\u{0bcd}'), '\u{f0a9}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ம','\u{0bcd}'), '\u{f044}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ய','\u{0bcd}'), '\u{f046}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ர','\u{0bcd}'), '\u{f0ec}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ல','\u{0bcd}'), '\u{f05f}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('வ','\u{0bcd}'), '\u{f0cb}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ழ','\u{0bcd}'), '\u{f0b5}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ள','\u{0bcd}'), '\u{f05e}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ற','\u{0bcd}'), '\u{f075}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ன','\u{0bcd}'), '\u{f05b}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஷ','\u{0bcd}'), '\u{f069}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஜ','\u{0bcd}'), '\u{f0eb}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஸ','\u{0bcd}'), '\u{f0fc}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஹ','\u{0bcd}'), '\u{f0e3}');
+    map.insert(('க','\u{0bcd}'), '\u{f0c2}');
+    map.insert(('ங','\u{0bcd}'), '\u{f0ba}');
+    map.insert(('ச','\u{0bcd}'), '\u{f0df}');
+    map.insert(('ஞ','\u{0bcd}'), '\u{f0de}');
+    map.insert(('ட','\u{0bcd}'), '\u{f0e2}');
+    map.insert(('ண','\u{0bcd}'), '\u{f0f5}');
+    map.insert(('த','\u{0bcd}'), '\u{f0dd}');
+    map.insert(('ந','\u{0bcd}'), '\u{f0cd}');
+    map.insert(('ப','\u{0bcd}'), '\u{f0a9}');
+    map.insert(('ம','\u{0bcd}'), '\u{f044}');
+    map.insert(('ய','\u{0bcd}'), '\u{f046}');
+    map.insert(('ர','\u{0bcd}'), '\u{f0ec}');
+    map.insert(('ல','\u{0bcd}'), '\u{f05f}');
+    map.insert(('வ','\u{0bcd}'), '\u{f0cb}');
+    map.insert(('ழ','\u{0bcd}'), '\u{f0b5}');
+    map.insert(('ள','\u{0bcd}'), '\u{f05e}');
+    map.insert(('ற','\u{0bcd}'), '\u{f075}');
+    map.insert(('ன','\u{0bcd}'), '\u{f05b}');
+    map.insert(('ஷ','\u{0bcd}'), '\u{f069}');
+    map.insert(('ஜ','\u{0bcd}'), '\u{f0eb}');
+    map.insert(('ஸ','\u{0bcd}'), '\u{f0fc}');
+    map.insert(('ஹ','\u{0bcd}'), '\u{f0e3}');
 
     //I kuril
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('க','\u{0bbf}'), '\u{f0fe}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ச','\u{0bbf}'), '\u{f045}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ட','\u{0bbf}'), '\u{f0bd}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ண','\u{0bbf}'), '\u{f0e8}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('த','\u{0bbf}'), '\u{f05d}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ந','\u{0bbf}'), '\u{f057}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ப','\u{0bbf}'), '\u{f0b8}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ம','\u{0bbf}'), '\u{f074}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ய','\u{0bbf}'), '\u{f06c}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ர','\u{0bbf}'), '\u{f0f6}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ல','\u{0bbf}'), '\u{f06f}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('வ','\u{0bbf}'), '\u{f073}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ழ','\u{0bbf}'), '\u{f061}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ள','\u{0bbf}'), '\u{f0b9}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ற','\u{0bbf}'), '\u{f0a4}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ன','\u{0bbf}'), '\u{f04d}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஷ','\u{0bbf}'), '\u{f0b4}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஜ','\u{0bbf}'), '\u{f0f7}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஸ','\u{0bbf}'), '\u{f04c}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஹ','\u{0bbf}'), '\u{f04e}');
+    map.insert(('க','\u{0bbf}'), '\u{f0fe}');
+    map.insert(('ச','\u{0bbf}'), '\u{f045}');
+    map.insert(('ட','\u{0bbf}'), '\u{f0bd}');
+    map.insert(('ண','\u{0bbf}'), '\u{f0e8}');
+    map.insert(('த','\u{0bbf}'), '\u{f05d}');
+    map.insert(('ந','\u{0bbf}'), '\u{f057}');
+    map.insert(('ப','\u{0bbf}'), '\u{f0b8}');
+    map.insert(('ம','\u{0bbf}'), '\u{f074}');
+    map.insert(('ய','\u{0bbf}'), '\u{f06c}');
+    map.insert(('ர','\u{0bbf}'), '\u{f0f6}');
+    map.insert(('ல','\u{0bbf}'), '\u{f06f}');
+    map.insert(('வ','\u{0bbf}'), '\u{f073}');
+    map.insert(('ழ','\u{0bbf}'), '\u{f061}');
+    map.insert(('ள','\u{0bbf}'), '\u{f0b9}');
+    map.insert(('ற','\u{0bbf}'), '\u{f0a4}');
+    map.insert(('ன','\u{0bbf}'), '\u{f04d}');
+    map.insert(('ஷ','\u{0bbf}'), '\u{f0b4}');
+    map.insert(('ஜ','\u{0bbf}'), '\u{f0f7}');
+    map.insert(('ஸ','\u{0bbf}'), '\u{f04c}');
+    map.insert(('ஹ','\u{0bbf}'), '\u{f04e}');
 
 
     //I Nedil
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('க','\u{0bc0}'), '\u{f0ff}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ச','\u{0bc0}'), '\u{f0e6}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ட','\u{0bc0}'), '\u{f0cf}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ண','\u{0bc0}'), '\u{f0a7}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('த','\u{0bc0}'), '\u{f079}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ந','\u{0bc0}'), '\u{f0c0}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ப','\u{0bc0}'), '\u{f0ac}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ம','\u{0bc0}'), '\u{f02a}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ய','\u{0bc0}'), '\u{f058}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ர','\u{0bc0}'), '\u{f05a}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ல','\u{0bc0}'), '\u{f0dc}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('வ','\u{0bc0}'), '\u{f054}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ழ','\u{0bc0}'), '\u{f0d1}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ள','\u{0bc0}'), '\u{f043}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ற','\u{0bc0}'), '\u{f053}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ன','\u{0bc0}'), '\u{f0cc}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஷ','\u{0bc0}'), '\u{f055}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஜ','\u{0bc0}'), '\u{f0fd}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஸ','\u{0bc0}'), '\u{f0a2}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஹ','\u{0bc0}'), '\u{f0ea}');
+    map.insert(('க','\u{0bc0}'), '\u{f0ff}');
+    map.insert(('ச','\u{0bc0}'), '\u{f0e6}');
+    map.insert(('ட','\u{0bc0}'), '\u{f0cf}');
+    map.insert(('ண','\u{0bc0}'), '\u{f0a7}');
+    map.insert(('த','\u{0bc0}'), '\u{f079}');
+    map.insert(('ந','\u{0bc0}'), '\u{f0c0}');
+    map.insert(('ப','\u{0bc0}'), '\u{f0ac}');
+    map.insert(('ம','\u{0bc0}'), '\u{f02a}');
+    map.insert(('ய','\u{0bc0}'), '\u{f058}');
+    map.insert(('ர','\u{0bc0}'), '\u{f05a}');
+    map.insert(('ல','\u{0bc0}'), '\u{f0dc}');
+    map.insert(('வ','\u{0bc0}'), '\u{f054}');
+    map.insert(('ழ','\u{0bc0}'), '\u{f0d1}');
+    map.insert(('ள','\u{0bc0}'), '\u{f043}');
+    map.insert(('ற','\u{0bc0}'), '\u{f053}');
+    map.insert(('ன','\u{0bc0}'), '\u{f0cc}');
+    map.insert(('ஷ','\u{0bc0}'), '\u{f055}');
+    map.insert(('ஜ','\u{0bc0}'), '\u{f0fd}');
+    map.insert(('ஸ','\u{0bc0}'), '\u{f0a2}');
+    map.insert(('ஹ','\u{0bc0}'), '\u{f0ea}');
 
     //U Kuril
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('க','\u{0bc1}'), '\u{f07a}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ச','\u{0bc1}'), '\u{f0b7}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ட','\u{0bc1}'), '\u{f07c}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ண','\u{0bc1}'), '\u{f062}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('த','\u{0bc1}'), '\u{f06d}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ந','\u{0bc1}'), '\u{f04f}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ப','\u{0bc1}'), '\u{f041}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ம','\u{0bc1}'), '\u{f078}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ய','\u{0bc1}'), '\u{f0a5}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ர','\u{0bc1}'), '\u{f0f2}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ல','\u{0bc1}'), '\u{f04b}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('வ','\u{0bc1}'), '\u{f0a1}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ழ','\u{0bc1}'), '\u{f0bf}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ள','\u{0bc1}'), '\u{f0d3}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ற','\u{0bc1}'), '\u{f0ae}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ன','\u{0bc1}'), '\u{f0d0}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஷ','\u{0bc1}'), '\u{f0d7}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஜ','\u{0bc1}'), '\u{f068}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஸ','\u{0bc1}'), '\u{f071}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஹ','\u{0bc1}'), '\u{f0f8}');
+    map.insert(('க','\u{0bc1}'), '\u{f07a}');
+    map.insert(('ச','\u{0bc1}'), '\u{f0b7}');
+    map.insert(('ட','\u{0bc1}'), '\u{f07c}');
+    map.insert(('ண','\u{0bc1}'), '\u{f062}');
+    map.insert(('த','\u{0bc1}'), '\u{f06d}');
+    map.insert(('ந','\u{0bc1}'), '\u{f04f}');
+    map.insert(('ப','\u{0bc1}'), '\u{f041}');
+    map.insert(('ம','\u{0bc1}'), '\u{f078}');
+    map.insert(('ய','\u{0bc1}'), '\u{f0a5}');
+    map.insert(('ர','\u{0bc1}'), '\u{f0f2}');
+    map.insert(('ல','\u{0bc1}'), '\u{f04b}');
+    map.insert(('வ','\u{0bc1}'), '\u{f0a1}');
+    map.insert(('ழ','\u{0bc1}'), '\u{f0bf}');
+    map.insert(('ள','\u{0bc1}'), '\u{f0d3}');
+    map.insert(('ற','\u{0bc1}'), '\u{f0ae}');
+    map.insert(('ன','\u{0bc1}'), '\u{f0d0}');
+    map.insert(('ஷ','\u{0bc1}'), '\u{f0d7}');
+    map.insert(('ஜ','\u{0bc1}'), '\u{f068}');
+    map.insert(('ஸ','\u{0bc1}'), '\u{f071}');
+    map.insert(('ஹ','\u{0bc1}'), '\u{f0f8}');
 
     //U Nedil
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('க','\u{0bc2}'), '\u{f0ed}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ச','\u{0bc2}'), '\u{f0f3}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ட','\u{0bc2}'), '\u{f0f9}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ண','\u{0bc2}'), '\u{f049}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('த','\u{0bc2}'), '\u{f023}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ந','\u{0bc2}'), '\u{f0b1}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ப','\u{0bc2}'), '\u{f0af}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ம','\u{0bc2}'), '\u{f04a}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ய','\u{0bc2}'), '\u{f052}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ர','\u{0bc2}'), '\u{f0d4}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ல','\u{0bc2}'), '\u{f0d9}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('வ','\u{0bc2}'), '\u{f0c6}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ழ','\u{0bc2}'), '\u{f0f1}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ள','\u{0bc2}'), '\u{f06a}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ற','\u{0bc2}'), '\u{f047}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ன','\u{0bc2}'), '\u{f0fb}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஷ','\u{0bc2}'), '\u{f0a3}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஜ','\u{0bc2}'), '\u{f0c9}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஸ','\u{0bc2}'), '\u{f060}');
-    UNIC_STMZH_MAP_TUPLE_CHAR.insert(('ஹ','\u{0bc2}'), '\u{f0da}');
+    map.insert(('க','\u{0bc2}'), '\u{f0ed}');
+    map.insert(('ச','\u{0bc2}'), '\u{f0f3}');
+    map.insert(('ட','\u{0bc2}'), '\u{f0f9}');
+    map.insert(('ண','\u{0bc2}'), '\u{f049}');
+    map.insert(('த','\u{0bc2}'), '\u{f023}');
+    map.insert(('ந','\u{0bc2}'), '\u{f0b1}');
+    map.insert(('ப','\u{0bc2}'), '\u{f0af}');
+    map.insert(('ம','\u{0bc2}'), '\u{f04a}');
+    map.insert(('ய','\u{0bc2}'), '\u{f052}');
+    map.insert(('ர','\u{0bc2}'), '\u{f0d4}');
+    map.insert(('ல','\u{0bc2}'), '\u{f0d9}');
+    map.insert(('வ','\u{0bc2}'), '\u{f0c6}');
+    map.insert(('ழ','\u{0bc2}'), '\u{f0f1}');
+    map.insert(('ள','\u{0bc2}'), '\u{f06a}');
+    map.insert(('ற','\u{0bc2}'), '\u{f047}');
+    map.insert(('ன','\u{0bc2}'), '\u{f0fb}');
+    map.insert(('ஷ','\u{0bc2}'), '\u{f0a3}');
+    map.insert(('ஜ','\u{0bc2}'), '\u{f0c9}');
+    map.insert(('ஸ','\u{0bc2}'), '\u{f060}');
+    map.insert(('ஹ','\u{0bc2}'), '\u{f0da}');
+    map
+}
 
-    let mut data = source;
+#[wasm_bindgen]
+pub fn convert_unic_stmzh(source: &str) -> String {
+    let unic_stmzh_map_char_char = unic_stmzh_map_char_char();
+    let unic_stmzh_map_tuple_char = unic_stmzh_map_tuple_char();
+
+    let source = with_fallbacks(&normalize::normalize(source), |_| false);
+    let mut data = source.as_str();
         let it = std::iter::from_fn(move || {
             match parse_entity(data) {
                 Ok((i, o)) => {
@@ -404,9 +619,9 @@ pub fn convert_unic_stmzh(source: &str) -> String {
     for entity in it {
         use TamilDetailedEntity::*;
         match entity {
-            Consonant (c) | Vowel(c) => output.push(*UNIC_STMZH_MAP_CHAR_CHAR.get(&c).unwrap()),
+            Consonant (c) | Vowel(c) => output.push(*unic_stmzh_map_char_char.get(&c).unwrap()),
             SeparateEntity((c,m)) => {
-                let res = UNIC_STMZH_MAP_TUPLE_CHAR.get(&(c,m));
+                let res = unic_stmzh_map_tuple_char.get(&(c,m));
                 match res {
                     Some(c) => output.push(*c),
                     None => { //In case of character not having equivalent in stmzh, we just output the original unicode.
@@ -417,18 +632,126 @@ pub fn convert_unic_stmzh(source: &str) -> String {
             },
             Other(c) => output.push(c),
             ComposedEntity((c,_, m)) => {
-                let stmzhchar = UNIC_STMZH_MAP_CHAR_CHAR.get(&c).unwrap();
+                let stmzhchar = unic_stmzh_map_char_char.get(&c).unwrap();
                 output.push_str(&conv_composed_entity(*stmzhchar, m));
             },
             SpecialEntity(ustring) => output.push_str(&conv_special_entity(ustring).unwrap()),
             MarkedSpecialConsonant((ustring, _, mark)) => output.push_str(&conv_marked_special_entity(ustring, mark).unwrap()),
-
-            _ => ()
+            Mark((_, m)) => match conv_lone_mark(m) {
+                Some(s) => output.push_str(s),
+                None => output.push(m),
+            },
+            Digit(c) | Number(c) | Fraction(c) | Sign(c) => output.push(c), //what is left after the fallbacks
         }
     }
     output.to_string()
 }
 
+//Every glyph sequence convert_unic_stmzh can produce, with the Unicode text it stands for
+pub fn stmzh_unic_map() -> HashMap<String, String> {
+    let unic_stmzh_map_char_char = unic_stmzh_map_char_char();
+    let mut map = HashMap::new();
+    for (u, s) in &unic_stmzh_map_char_char {
+        map.insert(s.to_string(), u.to_string());
+    }
+    for ((c, m), s) in unic_stmzh_map_tuple_char() {
+        map.insert(s.to_string(), [c, m].iter().collect());
+    }
+    for c in CONSONANTS.chars() {
+        if let Some(&stmzhchar) = unic_stmzh_map_char_char.get(&c) {
+            for m in NON_RIDING_MARKS.chars() {
+                map.insert(conv_composed_entity(stmzhchar, m), [c, m].iter().collect());
+            }
+        }
+    }
+    for m in NON_RIDING_MARKS.chars().chain([AU_LENGTH_MARK]) {
+        if let Some(s) = conv_lone_mark(m) {
+            map.entry(s.to_string()).or_insert_with(|| m.to_string());
+        }
+    }
+    for ustring in &["ஸ்ரீ", "க்ஷ"] {
+        map.insert(conv_special_entity(ustring).unwrap(), ustring.to_string());
+    }
+    for m in RIDING_MARKS.chars().chain(NON_RIDING_MARKS.chars()) {
+        if let Some(s) = conv_marked_special_entity("க்ஷ", m) {
+            map.insert(s, format!("க்ஷ{}", m));
+        }
+    }
+    add_separated_conjuncts(&mut map);
+    map
+}
+
+//க்‌ஷ and ஸ்‌ரீ written with separate glyphs (க் then ஷ) decode with the ZWNJ that keeps
+//them apart, rather than as the conjunct
+pub(crate) fn add_separated_conjuncts(map: &mut HashMap<String, String>) {
+    for (first, second) in [("க்", "ஷ"), ("ஸ்", "ரீ")] {
+        let first_glyphs = match map.iter().find(|(_, u)| u.as_str() == first) {
+            Some((glyphs, _)) => glyphs.clone(),
+            None => continue,
+        };
+        let separated: Vec<(String, String)> = map.iter()
+            .filter(|(_, u)| u.starts_with(second))
+            .map(|(glyphs, u)| (format!("{}{}", first_glyphs, glyphs), format!("{}\u{200c}{}", first, u)))
+            .collect();
+        map.extend(separated);
+    }
+}
+
+//Glyphs are matched greedily (longest sequence first), so that the preceding glyph of
+//E, EE, AI is picked up together with the consonant it marks. Anything else is kept as is.
+pub(crate) fn decode_greedy(map: &HashMap<String, String>, source: &str) -> String {
+    let longest = map.keys().map(|k| k.chars().count()).max().unwrap_or(1);
+
+    let mut output = String::with_capacity(source.len());
+    let mut data = source;
+    while let Some(first) = data.chars().next() {
+        let matched = (1..=longest).rev().find_map(|n| {
+            let end = data.char_indices().nth(n).map_or(data.len(), |(i, _)| i);
+            map.get(&data[..end]).map(|u| (end, u))
+        });
+        match matched {
+            Some((end, u)) => {
+                output.push_str(u);
+                data = &data[end..];
+            }
+            None => {
+                output.push(first);
+                data = &data[first.len_utf8()..];
+            }
+        }
+    }
+    output
+}
+
+//Reverse of convert_unic_stmzh
+#[wasm_bindgen]
+pub fn convert_stmzh_unic(source: &str) -> String {
+    decode_greedy(&stmzh_unic_map(), source)
+}
+
+//convert_unic_stmzh, written with the bytes of the symbol font instead of U+F0XX (for 8-bit text)
+#[wasm_bindgen]
+pub fn convert_unic_stmzh_low_byte(source: &str) -> String {
+    low_byte::pua_to_low_byte(&convert_unic_stmzh(source))
+}
+
+//Reverse of convert_unic_stmzh_low_byte. Bytes that are no stmzh glyph (digits, punctuation) are kept.
+#[wasm_bindgen]
+pub fn convert_stmzh_low_byte_unic(source: &str) -> String {
+    low_byte::pua_to_low_byte(&convert_stmzh_unic(&low_byte::low_byte_to_pua(source)))
+}
+
+//Legacy 8-bit stmzh text (e.g. an old .txt file), one byte per glyph
+#[wasm_bindgen]
+pub fn convert_unic_stmzh_bytes(source: &str) -> Vec<u8> {
+    low_byte::low_byte_to_bytes(&convert_unic_stmzh_low_byte(source))
+}
+
+#[wasm_bindgen]
+pub fn convert_stmzh_bytes_unic(source: &[u8]) -> String {
+    convert_stmzh_low_byte_unic(&low_byte::bytes_to_low_byte(source))
+}
+
 pub fn add_event_input() {
     let window = web_sys::window().expect("global window does not exist");
     let document = window.document().expect("window must have a document");
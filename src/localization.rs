@@ -0,0 +1,310 @@
+use crate::fonts::FontConversion;
+use crate::low_byte::bytes_to_low_byte;
+
+//Keys of the messages to convert: patterns where * stands for any run of characters. A JSON
+//path (menu.items.0.label) is matched with a pattern or its prefixes ending at a dot (menu.*
+//matches every key under menu); a PO message is matched by its msgctxt, or its msgid.
+#[derive(Debug, Clone, Default)]
+pub struct KeyFilter(pub Vec<String>);
+
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((head, tail)) => text.strip_prefix(head).is_some_and(|rest| {
+            (0..=rest.len()).filter(|i| rest.is_char_boundary(*i)).any(|i| glob_matches(tail, &rest[i..]))
+        }),
+    }
+}
+
+impl KeyFilter {
+    //"menu.*, title"
+    pub fn parse(spec: &str) -> Self {
+        KeyFilter(spec.split(',').map(str::trim).filter(|k| !k.is_empty()).map(str::to_string).collect())
+    }
+
+    pub fn matches(&self, key: &str) -> bool {
+        self.0.is_empty() || self.0.iter().any(|pattern| {
+            glob_matches(pattern, key)
+                || key.match_indices('.').any(|(i, _)| glob_matches(pattern, &key[..i]))
+        })
+    }
+}
+
+//Length of the placeholder or markup at the beginning of text: printf (%s, %1$d, %(name)s,
+//%{name}, %%), braces ({name}, {0}, {{name}}, ${name}) and HTML tags
+fn placeholder_length(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    match bytes.first()? {
+        b'%' => {
+            let rest = &text[1..];
+            if rest.starts_with('%') {
+                return Some(2);
+            }
+            if rest.starts_with('(') || rest.starts_with('{') {
+                let close = if rest.starts_with('(') { ')' } else { '}' };
+                let end = rest.find(close)? + 1;
+                let conversion = rest[end..].find(|c: char| c.is_ascii_alphabetic()).filter(|p| rest[end..end + p].bytes().all(|b| b"-+ #0.123456789".contains(&b)));
+                return Some(1 + end + conversion.map_or(0, |p| p + 1));
+            }
+            let spec = rest.find(|c: char| !(c.is_ascii_digit() || "$-+ #.*hlLqjzt".contains(c)))?;
+            let letter = rest[spec..].chars().next()?;
+            if "diouxXeEfFgGaAcspn@".contains(letter) && !rest[..spec].contains(' ') {
+                Some(1 + spec + 1)
+            } else {
+                None
+            }
+        }
+        b'$' if text[1..].starts_with('{') => text.find('}').map(|p| p + 1),
+        b'{' => {
+            let double = text.starts_with("{{");
+            let end = if double { text.find("}}")? + 2 } else { text.find('}')? + 1 };
+            let inner = text[..end].trim_matches(['{', '}']);
+            if !inner.is_empty() && !inner.contains(char::is_whitespace) {
+                Some(end)
+            } else {
+                None
+            }
+        }
+        b'<' if text[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/') => text.find('>').map(|p| p + 1),
+        _ => None,
+    }
+}
+
+//Message converted between its placeholders
+fn convert_message(message: &str, convert: &dyn Fn(&str) -> String) -> String {
+    let mut output = String::with_capacity(message.len());
+    let mut text_start = 0;
+    let mut i = 0;
+    while i < message.len() {
+        match placeholder_length(&message[i..]) {
+            Some(length) => {
+                output.push_str(&convert(&message[text_start..i]));
+                output.push_str(&message[i..i + length]);
+                i += length;
+                text_start = i;
+            }
+            None => i += message[i..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    output.push_str(&convert(&message[text_start..]));
+    output
+}
+
+fn read_text(input: &[u8]) -> String {
+    match std::str::from_utf8(input) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes_to_low_byte(input),
+    }
+}
+
+//C escapes of PO strings
+fn po_unescape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some('t') => output.push('\t'),
+            Some('r') => output.push('\r'),
+            Some(c) => output.push(c),
+            None => output.push('\\'),
+        }
+    }
+    output
+}
+
+fn po_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t").replace('\r', "\\r")
+}
+
+//The quoted string of a PO line, with what comes before and after it
+fn po_string(line: &str) -> Option<(&str, &str, &str)> {
+    let start = line.find('"')?;
+    let end = line.rfind('"').filter(|end| *end > start)?;
+    Some((&line[..start + 1], &line[start + 1..end], &line[end..]))
+}
+
+//Converts the msgstr strings of a PO file (the header, for msgid "", is kept). Each quoted
+//string of a wrapped message is converted on its own: msgcat wraps messages between words.
+//Comments, msgid, msgctxt and the layout of the file are kept as they are.
+pub fn convert_po(input: &[u8], keys: &KeyFilter, conversion: &FontConversion) -> Result<Vec<u8>, String> {
+    let text = read_text(input);
+    let convert = conversion.document_converter(&text);
+    let mut output = String::with_capacity(text.len());
+    let mut keyword = "";
+    let (mut msgctxt, mut msgid): (Option<String>, String) = (None, String::new());
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('"') {
+            //continuation of the string of the keyword
+        } else if let Some(word) = trimmed.split_whitespace().next().filter(|w| w.starts_with("msg")) {
+            keyword = if word.starts_with("msgstr") { "msgstr" } else { word };
+            match keyword {
+                "msgctxt" => {
+                    msgctxt = Some(String::new());
+                    msgid.clear();
+                }
+                "msgid" => msgid.clear(),
+                _ => (),
+            }
+        } else {
+            if trimmed.trim().is_empty() {
+                msgctxt = None;
+            }
+            keyword = "";
+            output.push_str(line);
+            continue;
+        }
+        let (before, string, after) = match po_string(line) {
+            Some(parts) => parts,
+            None => {
+                output.push_str(line);
+                continue;
+            }
+        };
+        match keyword {
+            "msgctxt" => msgctxt.get_or_insert_with(String::new).push_str(&po_unescape(string)),
+            "msgid" => msgid.push_str(&po_unescape(string)),
+            _ => (),
+        }
+        let key = msgctxt.as_deref().unwrap_or(&msgid);
+        if keyword == "msgstr" && !msgid.is_empty() && !string.is_empty() && keys.matches(key) {
+            output.push_str(before);
+            output.push_str(&po_escape(&convert_message(&po_unescape(string), &convert)));
+            output.push_str(after);
+        } else {
+            output.push_str(line);
+        }
+    }
+    Ok(output.into_bytes())
+}
+
+//Length of the JSON string at the beginning of text, quotes included
+fn json_string_length(text: &str) -> usize {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return i + 1,
+            _ => (),
+        }
+    }
+    text.len()
+}
+
+fn json_escape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len() + 2);
+    output.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+enum Container {
+    Object { key: String, expecting_key: bool },
+    Array { index: usize },
+}
+
+//Converts the string values of a JSON document whose key path is matched by keys. Keys,
+//numbers, the layout of the file and the strings left as they are (escapes included) are kept.
+pub fn convert_json(input: &[u8], keys: &KeyFilter, conversion: &FontConversion) -> Result<Vec<u8>, String> {
+    let text = read_text(input);
+    serde_json::from_str::<serde_json::Value>(&text).map_err(|e| e.to_string())?;
+    let convert = conversion.document_converter(&text);
+    let mut output = String::with_capacity(text.len());
+    let mut stack: Vec<Container> = Vec::new();
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let c = rest.chars().next().unwrap_or_default();
+        let length = match c {
+            '"' => json_string_length(rest),
+            _ => c.len_utf8(),
+        };
+        let token = &rest[..length];
+        match c {
+            '{' => stack.push(Container::Object { key: String::new(), expecting_key: true }),
+            '[' => stack.push(Container::Array { index: 0 }),
+            '}' | ']' => {
+                stack.pop();
+            }
+            ',' => match stack.last_mut() {
+                Some(Container::Object { expecting_key, .. }) => *expecting_key = true,
+                Some(Container::Array { index }) => *index += 1,
+                None => (),
+            },
+            ':' => {
+                if let Some(Container::Object { expecting_key, .. }) = stack.last_mut() {
+                    *expecting_key = false;
+                }
+            }
+            _ => (),
+        }
+        if c == '"' {
+            let value: String = serde_json::from_str(token).map_err(|e| e.to_string())?;
+            if let Some(Container::Object { key, expecting_key: true }) = stack.last_mut() {
+                *key = value;
+                output.push_str(token);
+            } else {
+                let path: Vec<String> = stack.iter().map(|container| match container {
+                    Container::Object { key, .. } => key.clone(),
+                    Container::Array { index } => index.to_string(),
+                }).collect();
+                let converted = if keys.matches(&path.join(".")) { convert_message(&value, &convert) } else { value.clone() };
+                if converted == value {
+                    output.push_str(token);
+                } else {
+                    output.push_str(&json_escape(&converted));
+                }
+            }
+        } else {
+            output.push_str(token);
+        }
+        i += length;
+    }
+    Ok(output.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::fonts::Direction;
+
+    fn conversion() -> FontConversion {
+        FontConversion::new(Encoding::stmzh(), Direction::Decode)
+    }
+
+    #[test]
+    fn converts_po_messages() {
+        let po = "msgid \"\"\nmsgstr \"Content-Type: text/plain; charset=UTF-8\\n\"\n\n#: main.c:3\nmsgid \"Hello %s\"\nmsgstr \"\u{f0d8}\u{f0ef}\u{f056} %s {name}\"\n\"\u{f0ef}\\n\"\n\nmsgctxt \"menu\"\nmsgid \"File\"\nmsgstr \"\u{f0ef}\"\n";
+        let converted = String::from_utf8(convert_po(po.as_bytes(), &KeyFilter::default(), &conversion()).unwrap()).unwrap();
+        assert_eq!(converted, "msgid \"\"\nmsgstr \"Content-Type: text/plain; charset=UTF-8\\n\"\n\n#: main.c:3\nmsgid \"Hello %s\"\nmsgstr \"கொ %s {name}\"\n\"க\\n\"\n\nmsgctxt \"menu\"\nmsgid \"File\"\nmsgstr \"க\"\n");
+        let converted = String::from_utf8(convert_po(po.as_bytes(), &KeyFilter::parse("menu"), &conversion()).unwrap()).unwrap();
+        assert!(converted.contains("msgstr \"\u{f0d8}\u{f0ef}\u{f056} %s {name}\"") && converted.ends_with("msgstr \"க\"\n"));
+    }
+
+    #[test]
+    fn converts_json_values() {
+        let json = "{\n  \"title\": \"\\uf0ef\",\n  \"menu\": {\"items\": [\"\u{f0d8}\u{f0ef}\u{f056} %1$d <b>{count}</b>\", \"\u{f0ef}\"]},\n  \"\u{f0ef}\": 1\n}";
+        let converted = String::from_utf8(convert_json(json.as_bytes(), &KeyFilter::parse("menu.*"), &conversion()).unwrap()).unwrap();
+        assert_eq!(converted, "{\n  \"title\": \"\\uf0ef\",\n  \"menu\": {\"items\": [\"கொ %1$d <b>{count}</b>\", \"க\"]},\n  \"\u{f0ef}\": 1\n}");
+        assert!(KeyFilter::parse("menu.items").matches("menu.items.1"));
+        assert!(!KeyFilter::parse("menu.items").matches("menu.itemsx"));
+    }
+}
@@ -0,0 +1,71 @@
+//Symbol fonts draw byte 0xXX. Word stores such text as U+F0XX (the private use area),
+//while 8-bit files and older software store the byte itself, read as Windows-1252.
+
+//Windows-1252 characters for bytes 0x80..=0x9F. The five bytes it leaves undefined
+//are kept as the C1 control of the same value, as Windows does.
+const CP1252_80_9F: [char; 32] = [
+    '\u{20ac}', '\u{0081}', '\u{201a}', '\u{0192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02c6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008d}', '\u{017d}', '\u{008f}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02dc}', '\u{2122}', '\u{0161}', '\u{203a}', '\u{0153}', '\u{009d}', '\u{017e}', '\u{0178}',
+];
+
+pub fn cp1252_char(byte: u8) -> char {
+    match byte {
+        0x80..=0x9f => CP1252_80_9F[(byte - 0x80) as usize],
+        _ => byte as char,
+    }
+}
+
+pub fn cp1252_byte(c: char) -> Option<u8> {
+    match c as u32 {
+        0x00..=0x7f | 0xa0..=0xff => Some(c as u8),
+        _ => CP1252_80_9F.iter().position(|&d| d == c).map(|i| 0x80 + i as u8),
+    }
+}
+
+//Glyph of a symbol font, as stored by Word
+pub fn is_pua(c: char) -> bool {
+    ('\u{f000}'..='\u{f0ff}').contains(&c)
+}
+
+//U+F021..=U+F0FF -> the Windows-1252 character of byte 0x21..=0xFF. U+F020 becomes a space.
+pub fn pua_to_low_byte(source: &str) -> String {
+    source.chars().map(|c| match c as u32 {
+        0xf020..=0xf0ff => cp1252_char((c as u32 - 0xf000) as u8),
+        _ => c,
+    }).collect()
+}
+
+//Reverse of pua_to_low_byte. Spaces and control characters (line breaks, tabs) are kept,
+//as well as anything outside Windows-1252.
+pub fn low_byte_to_pua(source: &str) -> String {
+    source.chars().map(|c| match cp1252_byte(c) {
+        Some(byte) if byte > 0x20 && byte != 0x7f => std::char::from_u32(0xf000 + byte as u32).unwrap(),
+        _ => c,
+    }).collect()
+}
+
+//An 8-bit file read as Windows-1252
+pub fn bytes_to_low_byte(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| cp1252_char(b)).collect()
+}
+
+//Characters outside Windows-1252 (e.g. letters the encoding has no glyph for) become '?'
+pub fn low_byte_to_bytes(source: &str) -> Vec<u8> {
+    source.chars().map(|c| cp1252_byte(c).unwrap_or(b'?')).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn low_byte_round_trip() {
+        assert_eq!(pua_to_low_byte("\u{f0ef}\u{f056} \u{f080}\u{f041}\n"), "\u{ef}V \u{20ac}A\n");
+        assert_eq!(low_byte_to_pua("\u{ef}V \u{20ac}A\n"), "\u{f0ef}\u{f056} \u{f080}\u{f041}\n");
+        for byte in 0..=255u8 {
+            assert_eq!(cp1252_byte(cp1252_char(byte)), Some(byte));
+        }
+    }
+}
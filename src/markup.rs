@@ -0,0 +1,300 @@
+use crate::fonts::FontConversion;
+use crate::low_byte::bytes_to_low_byte;
+
+//LaTeX environments whose content is not prose
+const VERBATIM_ENVIRONMENTS: [&str; 5] = ["verbatim", "Verbatim", "lstlisting", "minted", "comment"];
+const MATH_ENVIRONMENTS: [&str; 9] = ["equation", "align", "alignat", "gather", "multline", "flalign", "math", "displaymath", "eqnarray"];
+//Environments taking a column specification
+const TABULAR_ENVIRONMENTS: [&str; 4] = ["tabular", "array", "longtable", "tabularx"];
+//Commands whose arguments are names, keys, paths or code, and how many braced ones they take
+const RAW_ARGUMENT_COMMANDS: [(&str, usize); 30] = [
+    ("label", 1), ("ref", 1), ("eqref", 1), ("pageref", 1), ("autoref", 1), ("cref", 1),
+    ("cite", 1), ("citep", 1), ("citet", 1), ("nocite", 1), ("url", 1), ("href", 1),
+    ("includegraphics", 1), ("input", 1), ("include", 1), ("usepackage", 1), ("documentclass", 1),
+    ("bibliography", 1), ("bibliographystyle", 1), ("setmainfont", 1), ("setsansfont", 1), ("setmonofont", 1),
+    ("newfontfamily", 2), ("newcommand", 1), ("renewcommand", 1), ("newenvironment", 1),
+    ("color", 1), ("textcolor", 1), ("definecolor", 3), ("setlength", 2),
+];
+
+//Converts the prose given to it, and copies the rest as it is
+struct Prose<'a> {
+    convert: Box<dyn Fn(&str) -> String + 'a>,
+    output: String,
+    pending: String,
+}
+
+impl<'a> Prose<'a> {
+    fn new(input: &str, conversion: &'a FontConversion) -> Self {
+        Prose { convert: conversion.document_converter(input), output: String::with_capacity(input.len()), pending: String::new() }
+    }
+
+    fn prose(&mut self, text: &str) {
+        self.pending.push_str(text);
+    }
+
+    fn raw(&mut self, text: &str) {
+        if !self.pending.is_empty() {
+            self.output.push_str(&(self.convert)(&self.pending));
+            self.pending.clear();
+        }
+        self.output.push_str(text);
+    }
+
+    fn finish(mut self) -> String {
+        self.raw("");
+        self.output
+    }
+}
+
+fn read_text(input: &[u8]) -> String {
+    match std::str::from_utf8(input) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes_to_low_byte(input),
+    }
+}
+
+fn char_length(text: &str) -> usize {
+    text.chars().next().map_or(1, char::len_utf8)
+}
+
+//Length of the bare URL at the beginning of text (up to a space or a closing bracket)
+fn url_length(text: &str) -> Option<usize> {
+    if !["http://", "https://", "ftp://", "mailto:", "www."].iter().any(|scheme| text.starts_with(scheme)) {
+        return None;
+    }
+    let end = text.find(|c: char| c.is_whitespace() || c == ')' || c == '>' || c == ']').unwrap_or(text.len());
+    Some(text[..end].trim_end_matches(['.', ',', ';', ':', '!', '?', '"', '\'']).len())
+}
+
+//Inline code, autolinks and HTML tags, link destinations and bare URLs are copied, the rest of
+//the line is prose
+fn markdown_inline(line: &str, prose: &mut Prose) {
+    let mut i = 0;
+    let mut word_start = true;
+    while i < line.len() {
+        let rest = &line[i..];
+        let raw_length = if rest.starts_with('`') {
+            let ticks = rest.len() - rest.trim_start_matches('`').len();
+            let fence = &rest[..ticks];
+            rest[ticks..].find(fence).filter(|p| !rest[ticks + p + ticks..].starts_with('`')).map(|p| ticks + p + ticks).or(Some(ticks))
+        } else if rest.starts_with('<') && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!') {
+            rest.find('>').map(|p| p + 1)
+        } else if rest.starts_with("](") {
+            prose.prose("]");
+            i += 1;
+            let mut depth = 0;
+            let end = rest[1..].char_indices().find(|(_, c)| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => (),
+                }
+                depth == 0
+            });
+            Some(end.map_or(rest.len() - 1, |(p, _)| p + 1))
+        } else if rest.starts_with('\\') && rest[1..].starts_with(|c: char| c.is_ascii_punctuation()) {
+            Some(2)
+        } else if word_start {
+            url_length(rest)
+        } else {
+            None
+        };
+        let length = match raw_length {
+            Some(length) => {
+                prose.raw(&line[i..i + length]);
+                length
+            }
+            None => {
+                let length = char_length(rest);
+                prose.prose(&rest[..length]);
+                length
+            }
+        };
+        word_start = line[i..i + length].ends_with(|c: char| c.is_whitespace() || c == '(' || c == '[');
+        i += length;
+    }
+}
+
+//Converts the prose of a Markdown document. Fenced and indented code blocks, inline code,
+//link destinations, reference definitions, URLs and HTML tags are kept as they are.
+pub fn convert_markdown(input: &[u8], conversion: &FontConversion) -> Result<Vec<u8>, String> {
+    let text = read_text(input);
+    let mut prose = Prose::new(&text, conversion);
+    let mut fence: Option<String> = None;
+    let mut code_block_allowed = true; //after a blank line or in an indented code block
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[content.len()..];
+        let trimmed = content.trim_start();
+        let indented = content.starts_with("    ") || content.starts_with('\t');
+        if let Some(marker) = &fence {
+            if trimmed.starts_with(marker.as_str()) && trimmed.trim_end().chars().all(|c| marker.starts_with(c)) {
+                fence = None;
+            }
+            prose.raw(line);
+        } else if !indented && (trimmed.starts_with("```") || trimmed.starts_with("~~~")) {
+            let fence_char = trimmed.chars().next().unwrap_or('`');
+            fence = Some(trimmed.chars().take_while(|c| *c == fence_char).collect());
+            prose.raw(line);
+        } else if (indented && code_block_allowed && !trimmed.is_empty()) || (trimmed.starts_with('[') && trimmed.contains("]:")) {
+            //indented code, or a link reference definition
+            prose.raw(line);
+        } else {
+            markdown_inline(content, &mut prose);
+            prose.raw(ending);
+        }
+        if fence.is_none() {
+            code_block_allowed = trimmed.is_empty() || (indented && code_block_allowed);
+        }
+    }
+    Ok(prose.finish().into_bytes())
+}
+
+//Length of the braced group at the beginning of text, nested groups included
+fn group_length(text: &str) -> usize {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => (),
+        }
+    }
+    text.len()
+}
+
+//Length of the optional ([...]) and braced arguments at the beginning of text, braced ones
+//up to count
+fn arguments_length(text: &str, count: usize) -> usize {
+    let mut i = 0;
+    let mut braced = 0;
+    loop {
+        let rest = &text[i..];
+        let spaces = rest.len() - rest.trim_start_matches([' ', '\t']).len();
+        let rest = &rest[spaces..];
+        if rest.starts_with('[') {
+            i += spaces + rest.find(']').map_or(rest.len(), |p| p + 1);
+        } else if rest.starts_with('{') && braced < count {
+            i += spaces + group_length(rest);
+            braced += 1;
+        } else {
+            return i;
+        }
+    }
+}
+
+//Length of the LaTeX that isn't prose at the beginning of text, if it is not prose
+fn latex_raw_length(text: &str) -> Option<usize> {
+    let find_end = |from: usize, end: &str| text[from..].find(end).map_or(text.len(), |p| from + p + end.len());
+    if text.starts_with('%') {
+        return Some(text.find('\n').unwrap_or(text.len()));
+    }
+    if text.starts_with("$$") {
+        return Some(find_end(2, "$$"));
+    }
+    if let Some(math) = text.strip_prefix('$') {
+        let end = math.char_indices().find(|(p, c)| *c == '$' && !math[..*p].ends_with('\\'));
+        return Some(end.map_or(text.len(), |(p, _)| p + 2));
+    }
+    if text.starts_with("\\(") {
+        return Some(find_end(2, "\\)"));
+    }
+    if text.starts_with("\\[") {
+        return Some(find_end(2, "\\]"));
+    }
+    if text.starts_with(['{', '}', '&', '~', '^', '_', '#']) {
+        return Some(1);
+    }
+    let command = text.strip_prefix('\\')?;
+    let name_length = command.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(command.len());
+    if name_length == 0 {
+        return Some(1 + char_length(command)); //\%, \\, \{...
+    }
+    let name = &command[..name_length];
+    let after = 1 + name_length;
+    match name {
+        "verb" => {
+            let delimiter = text[after..].trim_start_matches('*').chars().next()?;
+            let start = after + text[after..].find(delimiter)? + delimiter.len_utf8();
+            Some(text[start..].find(delimiter).map_or(text.len(), |p| start + p + delimiter.len_utf8()))
+        }
+        "begin" => {
+            let group = after + group_length(&text[after..]);
+            let environment = text[after..group].trim_matches(['{', '}']);
+            let base = environment.trim_end_matches('*');
+            if VERBATIM_ENVIRONMENTS.contains(&base) || MATH_ENVIRONMENTS.contains(&base) {
+                Some(find_end(group, &format!("\\end{{{}}}", environment)))
+            } else if TABULAR_ENVIRONMENTS.contains(&base) {
+                Some(group + arguments_length(&text[group..], if base == "tabularx" { 2 } else { 1 }))
+            } else {
+                Some(group)
+            }
+        }
+        "end" => Some(after + group_length(&text[after..])),
+        _ => {
+            let name = name.trim_end_matches('*');
+            let count = RAW_ARGUMENT_COMMANDS.iter().find(|(command, _)| *command == name).map_or(0, |(_, count)| *count);
+            let starred = if text[after..].starts_with('*') { 1 } else { 0 };
+            Some(after + starred + if count > 0 { arguments_length(&text[after + starred..], count) } else { 0 })
+        }
+    }
+}
+
+//Converts the prose of a LaTeX document. Commands, the arguments of the commands taking names
+//or paths (\label, \cite, \url, \usepackage...), math, verbatim text and comments are kept as
+//they are; the arguments of other commands (\textbf{...}, \section{...}) are prose.
+pub fn convert_latex(input: &[u8], conversion: &FontConversion) -> Result<Vec<u8>, String> {
+    let text = read_text(input);
+    let mut prose = Prose::new(&text, conversion);
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let length = match latex_raw_length(rest) {
+            Some(length) => {
+                prose.raw(&rest[..length]);
+                length
+            }
+            None => {
+                let length = char_length(rest);
+                prose.prose(&rest[..length]);
+                length
+            }
+        };
+        i += length;
+    }
+    Ok(prose.finish().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::fonts::Direction;
+
+    fn encode(convert: fn(&[u8], &FontConversion) -> Result<Vec<u8>, String>, text: &str) -> String {
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Encode);
+        String::from_utf8(convert(text.as_bytes(), &conversion).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn converts_markdown_prose() {
+        let markdown = "# கொ\n\nக `க` [க](http://x.org/க) <https://க.in> www.க.com\n\n    க\n```\nக\n```\n[id]: http://க\n";
+        assert_eq!(encode(convert_markdown, markdown),
+            "# \u{f0d8}\u{f0ef}\u{f056}\n\n\u{f0ef} `க` [\u{f0ef}](http://x.org/க) <https://க.in> www.க.com\n\n    க\n```\nக\n```\n[id]: http://க\n");
+    }
+
+    #[test]
+    fn converts_latex_prose() {
+        let latex = "\\section{கொ} \\textbf{க} $க$ \\label{க} % க\n\\begin{equation}க\\end{equation}\\verb|க| \\url{க}";
+        assert_eq!(encode(convert_latex, latex),
+            "\\section{\u{f0d8}\u{f0ef}\u{f056}} \\textbf{\u{f0ef}} $க$ \\label{க} % க\n\\begin{equation}க\\end{equation}\\verb|க| \\url{க}");
+    }
+}
@@ -0,0 +1,96 @@
+const PULLI: char = '\u{0bcd}';
+const ZWNJ: char = '\u{200c}';
+const ZWJ: char = '\u{200d}';
+
+//Canonical compositions of the Tamil block: two-part vowel signs and ஔ
+const COMPOSITIONS: [(char, char, char); 4] = [
+    ('\u{0b92}', '\u{0bd7}', '\u{0b94}'), //ஒ + ௗ = ஔ
+    ('\u{0bc6}', '\u{0bbe}', '\u{0bca}'), //ெ + ா = ொ
+    ('\u{0bc7}', '\u{0bbe}', '\u{0bcb}'), //ே + ா = ோ
+    ('\u{0bc6}', '\u{0bd7}', '\u{0bcc}'), //ெ + ௗ = ௌ
+];
+
+//Joiner contexts that change the rendering: க்‍ஷ / க்‌ஷ and ஸ்‌ரீ
+const JOINED: [(char, char); 2] = [('க', 'ஷ'), ('ஸ', 'ர')];
+
+pub(crate) fn is_tamil(c: char) -> bool {
+    ('\u{0b80}'..='\u{0bff}').contains(&c)
+}
+
+//ெ + ா -> ொ
+pub(crate) fn compose_pair(first: char, second: char) -> Option<char> {
+    COMPOSITIONS.iter().find(|c| c.0 == first && c.1 == second).map(|c| c.2)
+}
+
+//Decomposed vowel signs composed (ெ+ா -> ொ), as Unicode NFC does for Tamil
+pub fn nfc(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        let last = output.chars().next_back();
+        match last.and_then(|last| compose_pair(last, c).map(|composed| (last, composed))) {
+            Some((first, composed)) => {
+                output.truncate(output.len() - first.len_utf8());
+                output.push(composed);
+            }
+            None => output.push(c),
+        }
+    }
+    output
+}
+
+//Two-part vowel signs decomposed (ொ -> ெ+ா), as Unicode NFD does for Tamil
+pub fn nfd(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        match COMPOSITIONS.iter().find(|(_, _, composed)| *composed == c) {
+            Some(&(first, second, _)) => {
+                output.push(first);
+                output.push(second);
+            }
+            None => output.push(c),
+        }
+    }
+    output
+}
+
+//Joiners next to Tamil letters are dropped, but for one directly after the pulli of a
+//conjunct that has a joined form (க்‍ஷ, ஸ்‌ரீ). Joiners in other scripts are kept.
+fn remove_redundant_joiners(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut output = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        if c != ZWJ && c != ZWNJ {
+            output.push(c);
+            continue;
+        }
+        let before = i.checked_sub(1).map(|i| chars[i]);
+        let after = chars.get(i + 1).copied();
+        let joined = before == Some(PULLI)
+            && i >= 2
+            && JOINED.iter().any(|&(first, second)| chars[i - 2] == first && after == Some(second));
+        let next_to_tamil = before.is_some_and(is_tamil) || after.is_some_and(is_tamil) || before == Some(ZWJ) || before == Some(ZWNJ);
+        if joined || !next_to_tamil {
+            output.push(c);
+        }
+    }
+    output
+}
+
+//Canonical form of Tamil text, used on input by the encoders: vowel signs composed,
+//ஶ்ரீ spelt ஸ்ரீ, and redundant joiners removed
+pub fn normalize(text: &str) -> String {
+    remove_redundant_joiners(&nfc(text).replace("ஶ்ரீ", "ஸ்ரீ"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_tamil() {
+        let decomposed = "க\u{0bc6}\u{0bbe} க\u{0bc7}\u{0bbe} க\u{0bc6}\u{0bd7} \u{0b92}\u{0bd7}";
+        assert_eq!(nfc(decomposed), "கொ கோ கௌ ஔ");
+        assert_eq!(nfd("கொ கோ கௌ ஔ"), decomposed);
+        assert_eq!(normalize("ஶ்ரீ க\u{200d}ா க்\u{200c}ஷ த்\u{200c}த a\u{200c}b"), "ஸ்ரீ கா க்\u{200c}ஷ த்த a\u{200c}b");
+    }
+}
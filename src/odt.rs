@@ -0,0 +1,233 @@
+use crate::fonts::FontConversion;
+use crate::package::{read_part, read_parts, rewrite_package};
+use crate::xml::{attribute, convert_text, rewrite_attributes, rewrite_xml, without_attribute};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::HashMap;
+
+//style:text-properties attributes naming a font declared in office:font-face-decls
+const FONT_NAME_ATTRIBUTES: [&[u8]; 3] = [b"style:font-name", b"style:font-name-asian", b"style:font-name-complex"];
+//and those giving the font family directly
+const FONT_FAMILY_ATTRIBUTES: [&[u8]; 3] = [b"fo:font-family", b"style:font-family-asian", b"style:font-family-complex"];
+
+#[derive(Debug)]
+struct StyleFont {
+    family: String,
+    parent: Option<String>,
+    font: Option<bool>, //does the style set a font, and is it a source font
+}
+
+//Fonts of the styles of the document (styles.xml and the automatic styles of content.xml)
+#[derive(Debug, Default)]
+struct OdtStyles {
+    font_faces: HashMap<String, String>, //style:name -> svg:font-family
+    styles: HashMap<String, StyleFont>,
+    defaults: HashMap<String, bool>, //style:family -> is the font of style:default-style a source font
+}
+
+impl OdtStyles {
+    fn is_source_font_face(&self, name: &str, conversion: &FontConversion) -> bool {
+        conversion.is_source_font(name)
+            || self.font_faces.get(name).is_some_and(|family| conversion.is_source_font(family))
+    }
+
+    //Some(is it a source font) if style:text-properties sets a font
+    fn text_properties_font(&self, e: &BytesStart, conversion: &FontConversion) -> Result<Option<bool>, String> {
+        let mut font = None;
+        for key in FONT_NAME_ATTRIBUTES.iter() {
+            if let Some(name) = attribute(e, key)? {
+                font = Some(font.unwrap_or(false) || self.is_source_font_face(&name, conversion));
+            }
+        }
+        for key in FONT_FAMILY_ATTRIBUTES.iter() {
+            if let Some(family) = attribute(e, key)? {
+                font = Some(font.unwrap_or(false) || conversion.is_source_font(&family));
+            }
+        }
+        Ok(font)
+    }
+
+    fn collect(&mut self, xml: &str, conversion: &FontConversion) -> Result<(), String> {
+        let mut reader = Reader::from_str(xml);
+        let mut current: Option<(Option<String>, StyleFont)> = None; //style being read: name (None for default-style)
+        loop {
+            let (e, empty) = match reader.read_event().map_err(|e| e.to_string())? {
+                Event::Eof => break,
+                Event::Start(e) => (e, false),
+                Event::Empty(e) => (e, true),
+                Event::End(e) if e.name().as_ref() == b"style:style" || e.name().as_ref() == b"style:default-style" => {
+                    if let Some((name, style)) = current.take() {
+                        self.insert(name, style);
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            match e.name().as_ref() {
+                b"style:font-face" => {
+                    if let (Some(name), Some(family)) = (attribute(&e, b"style:name")?, attribute(&e, b"svg:font-family")?) {
+                        self.font_faces.insert(name, family);
+                    }
+                }
+                b"style:style" | b"style:default-style" => {
+                    let name = attribute(&e, b"style:name")?;
+                    let style = StyleFont {
+                        family: attribute(&e, b"style:family")?.unwrap_or_default(),
+                        parent: attribute(&e, b"style:parent-style-name")?,
+                        font: None,
+                    };
+                    if empty {
+                        self.insert(name, style);
+                    } else {
+                        current = Some((name, style));
+                    }
+                }
+                b"style:text-properties" => {
+                    let font = self.text_properties_font(&e, conversion)?;
+                    if let Some((_, style)) = current.as_mut() {
+                        style.font = font.or(style.font);
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, name: Option<String>, style: StyleFont) {
+        match name {
+            Some(name) => {
+                self.styles.insert(name, style);
+            }
+            None => {
+                if let Some(font) = style.font {
+                    self.defaults.insert(style.family, font);
+                }
+            }
+        }
+    }
+
+    //Is text in the style set in a source font, through its parents and the default style
+    fn style_font(&self, name: &str) -> Option<bool> {
+        let mut style = self.styles.get(name)?;
+        for _ in 0..32 {
+            if style.font.is_some() {
+                return style.font;
+            }
+            match style.parent.as_ref().and_then(|p| self.styles.get(p)) {
+                Some(parent) => style = parent,
+                None => break,
+            }
+        }
+        self.defaults.get(&style.family).copied()
+    }
+}
+
+//Quoted the way LibreOffice writes font families with spaces
+fn font_family_value(font: &str) -> String {
+    if font.contains(' ') {
+        format!("'{}'", font)
+    } else {
+        font.to_string()
+    }
+}
+
+//style:font-face declaring a source font, and fonts of style:text-properties given
+//as a source font family, changed to the target font
+fn rewrite_font_declaration<'a>(e: BytesStart<'a>, styles: &OdtStyles, conversion: &FontConversion) -> Result<BytesStart<'a>, String> {
+    let target = font_family_value(conversion.target_font());
+    match e.name().as_ref() {
+        b"style:font-face" => {
+            let name = attribute(&e, b"style:name")?.unwrap_or_default();
+            if !styles.is_source_font_face(&name, conversion) {
+                return Ok(e);
+            }
+            let e = rewrite_attributes(&e, |key, _| if key == b"svg:font-family" { Some(target.clone()) } else { None })?
+                .unwrap_or(e);
+            //a symbol font declaration would keep LibreOffice from shaping the Unicode text
+            Ok(without_attribute(&e, b"style:font-charset"))
+        }
+        b"style:text-properties" => {
+            let rewritten = rewrite_attributes(&e, |key, value| {
+                if FONT_FAMILY_ATTRIBUTES.contains(&key) && conversion.is_source_font(value) {
+                    Some(target.clone())
+                } else {
+                    None
+                }
+            })?;
+            Ok(rewritten.unwrap_or(e))
+        }
+        _ => Ok(e),
+    }
+}
+
+fn convert_odt_xml(xml: &str, styles: &OdtStyles, conversion: &FontConversion) -> Result<String, String> {
+    let mut fonts: Vec<Option<bool>> = Vec::new(); //for each open element: the font its style sets
+    rewrite_xml(xml, |event, path| {
+        Ok(match event {
+            Event::Start(e) => {
+                fonts.push(attribute(&e, b"text:style-name")?.and_then(|name| styles.style_font(&name)));
+                Event::Start(rewrite_font_declaration(e, styles, conversion)?)
+            }
+            Event::Empty(e) => Event::Empty(rewrite_font_declaration(e, styles, conversion)?),
+            Event::End(e) => {
+                fonts.pop();
+                Event::End(e)
+            }
+            Event::Text(e) if path.iter().any(|n| n == b"text:p" || n == b"text:h") => {
+                let font = fonts.iter().rev().find_map(|f| *f).or_else(|| styles.defaults.get("paragraph").copied());
+                if font == Some(true) {
+                    convert_text(&e, |text| conversion.convert(text))?
+                } else {
+                    Event::Text(e)
+                }
+            }
+            event => event,
+        })
+    })
+}
+
+//Converts the text set, through its paragraph or span style, in a source font.
+//Font declarations and styles are rewritten to the target font.
+pub fn convert_odt(input: &[u8], conversion: &FontConversion) -> Result<Vec<u8>, String> {
+    let conversion = &conversion.for_document(&read_parts(input, |name| name == "styles.xml" || name == "content.xml")?);
+    let mut styles = OdtStyles::default();
+    //styles.xml first: the automatic styles of content.xml may inherit from its styles
+    for name in &["styles.xml", "content.xml"] {
+        if let Some(xml) = read_part(input, name)? {
+            styles.collect(&xml, conversion)?;
+        }
+    }
+    rewrite_package(input, |name, contents| {
+        if name != "styles.xml" && name != "content.xml" {
+            return Ok(None);
+        }
+        let xml = std::str::from_utf8(contents).map_err(|e| e.to_string())?;
+        convert_odt_xml(xml, &styles, conversion).map(|xml| Some(xml.into_bytes()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::fonts::Direction;
+
+    const STYLES: &str = r#"<office:document-styles><office:font-face-decls><style:font-face style:name="STMZH" svg:font-family="STMZH" style:font-charset="x-symbol"/></office:font-face-decls><office:styles><style:style style:name="Tamil" style:family="paragraph"><style:text-properties style:font-name="STMZH"/></style:style></office:styles></office:document-styles>"#;
+    const CONTENT: &str = r#"<office:document-content><office:automatic-styles><style:style style:name="P1" style:family="paragraph" style:parent-style-name="Tamil"/><style:style style:name="T1" style:family="text"><style:text-properties fo:font-family="Arial"/></style:style></office:automatic-styles><office:body><office:text><text:p text:style-name="P1">&#xF0D8;&#xF0EF;&#xF056;<text:span text:style-name="T1">A &amp; B</text:span></text:p><text:p>&#xF0EF;</text:p></office:text></office:body></office:document-content>"#;
+
+    #[test]
+    fn converts_text_in_legacy_styles() {
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        let mut styles = OdtStyles::default();
+        styles.collect(STYLES, &conversion).unwrap();
+        styles.collect(CONTENT, &conversion).unwrap();
+        assert_eq!(styles.style_font("P1"), Some(true));
+        assert_eq!(styles.style_font("T1"), Some(false));
+
+        let content = convert_odt_xml(CONTENT, &styles, &conversion).unwrap();
+        assert!(content.contains(r#"<text:p text:style-name="P1">கொ<text:span text:style-name="T1">A &amp; B</text:span></text:p><text:p>&#xF0EF;</text:p>"#));
+        let styles_xml = convert_odt_xml(STYLES, &styles, &conversion).unwrap();
+        assert!(styles_xml.contains(r#"<style:font-face style:name="STMZH" svg:font-family="Latha"/>"#));
+    }
+}
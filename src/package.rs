@@ -0,0 +1,84 @@
+use std::io::{Cursor, Read, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+//Contents of the entry name, if the package has it
+pub fn read_part(input: &[u8], name: &str) -> Result<Option<String>, String> {
+    let mut archive = ZipArchive::new(Cursor::new(input)).map_err(|e| e.to_string())?;
+    let mut file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).map_err(|e| format!("{}: {}", name, e))?;
+    Ok(Some(contents))
+}
+
+//Contents of the entries select picks, one after the other: a whole document, to look at
+//it at once
+pub fn read_parts<F: Fn(&str) -> bool>(input: &[u8], select: F) -> Result<String, String> {
+    let mut contents = String::new();
+    for name in part_names(input)?.iter().filter(|name| select(name)) {
+        contents.push_str(&read_part(input, name)?.unwrap_or_default());
+    }
+    Ok(contents)
+}
+
+//Names of the entries of the package
+pub fn part_names(input: &[u8]) -> Result<Vec<String>, String> {
+    let archive = ZipArchive::new(Cursor::new(input)).map_err(|e| e.to_string())?;
+    Ok(archive.file_names().map(str::to_string).collect())
+}
+
+//Copies a zip package (docx, odt, xlsx, epub), entries in the same order and with the same
+//compression. convert gets the name and contents of each entry, and returns the new contents
+//of the entries it rewrites; the others are copied untouched.
+pub fn rewrite_package<F>(input: &[u8], mut convert: F) -> Result<Vec<u8>, String>
+where
+    F: FnMut(&str, &[u8]) -> Result<Option<Vec<u8>>, String>,
+{
+    let mut archive = ZipArchive::new(Cursor::new(input)).map_err(|e| e.to_string())?;
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
+        let name = file.name().to_string();
+        let mut contents = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut contents).map_err(|e| format!("{}: {}", name, e))?;
+        match convert(&name, &contents).map_err(|e| format!("{}: {}", name, e))? {
+            Some(converted) => {
+                let mut options = SimpleFileOptions::default().compression_method(file.compression());
+                if let Some(time) = file.last_modified() {
+                    options = options.last_modified_time(time);
+                }
+                writer.start_file(name.as_str(), options).map_err(|e| e.to_string())?;
+                writer.write_all(&converted).map_err(|e| e.to_string())?;
+            }
+            None => {
+                drop(file);
+                let raw = archive.by_index_raw(i).map_err(|e| e.to_string())?;
+                writer.raw_copy_file(raw).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    let output = writer.finish().map_err(|e| e.to_string())?;
+    Ok(output.into_inner())
+}
+
+//Copies a zip package with the entry name put first, stored uncompressed, with contents
+//(the mimetype entry of an epub)
+pub fn put_first_stored(input: &[u8], name: &str, contents: &[u8]) -> Result<Vec<u8>, String> {
+    let mut archive = ZipArchive::new(Cursor::new(input)).map_err(|e| e.to_string())?;
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    writer.start_file(name, options).map_err(|e| e.to_string())?;
+    writer.write_all(contents).map_err(|e| e.to_string())?;
+    for i in 0..archive.len() {
+        let raw = archive.by_index_raw(i).map_err(|e| e.to_string())?;
+        if raw.name() != name {
+            writer.raw_copy_file(raw).map_err(|e| e.to_string())?;
+        }
+    }
+    let output = writer.finish().map_err(|e| e.to_string())?;
+    Ok(output.into_inner())
+}
@@ -0,0 +1,262 @@
+use crate::fonts::FontConversion;
+use crate::low_byte::{bytes_to_low_byte, is_pua};
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object};
+use std::collections::BTreeMap;
+
+//How the text shown with a font is read
+enum FontDecoder<'a> {
+    //legacy font with one-byte codes: the codes are the glyphs of the font
+    Legacy(&'a FontConversion),
+    //legacy font with its codes mapped (ToUnicode) to the U+F0XX glyphs
+    LegacyMapped(&'a FontConversion, lopdf::Encoding<'a>),
+    //any other font: its codes are mapped to Unicode
+    Unicode(lopdf::Encoding<'a>),
+    Unknown,
+}
+
+//"ABCDEF+STMZH,Bold" -> "STMZH"
+fn base_font_name(font: &Dictionary) -> String {
+    let name = font.get(b"BaseFont").and_then(Object::as_name).map(|n| String::from_utf8_lossy(n).into_owned()).unwrap_or_default();
+    let name = match name.split_once('+') {
+        Some((prefix, name)) if prefix.len() == 6 => name.to_string(),
+        _ => name,
+    };
+    name.split([',', '-']).next().unwrap_or_default().to_string()
+}
+
+fn font_decoder<'a>(font: &'a Dictionary, document: &'a Document, conversions: &'a [FontConversion]) -> FontDecoder<'a> {
+    let name = base_font_name(font);
+    let is_type0 = font.get(b"Subtype").and_then(Object::as_name).is_ok_and(|s| s == b"Type0");
+    let encoding = font.get_font_encoding(document).ok();
+    match (conversions.iter().find(|c| c.is_legacy_font(&name)), encoding) {
+        (Some(conversion), _) if !is_type0 => FontDecoder::Legacy(conversion),
+        (Some(conversion), Some(encoding)) => FontDecoder::LegacyMapped(conversion, encoding),
+        (Some(_), None) => FontDecoder::Unknown,
+        (None, Some(encoding)) => match conversions.first() {
+            //an unnamed symbol font whose codes map to U+F0XX is taken to be in the default encoding
+            Some(conversion) if font.get(b"ToUnicode").is_ok() && maps_to_pua(&encoding) => FontDecoder::LegacyMapped(conversion, encoding),
+            _ => FontDecoder::Unicode(encoding),
+        },
+        (None, None) => FontDecoder::Unknown,
+    }
+}
+
+fn maps_to_pua(encoding: &lopdf::Encoding) -> bool {
+    let codes: Vec<u8> = (0x21..=0xff).collect();
+    encoding.bytes_to_string(&codes).is_ok_and(|text| text.chars().filter(|c| is_pua(*c)).count() > codes.len() / 2)
+}
+
+impl FontDecoder<'_> {
+    fn is_legacy(&self) -> bool {
+        matches!(self, FontDecoder::Legacy(_) | FontDecoder::LegacyMapped(..))
+    }
+
+    //Text shown: legacy glyphs are kept as they are, to be decoded a whole run at a time
+    fn read(&self, codes: &[u8]) -> String {
+        match self {
+            FontDecoder::Legacy(_) => bytes_to_low_byte(codes),
+            FontDecoder::LegacyMapped(_, encoding) | FontDecoder::Unicode(encoding) => encoding.bytes_to_string(codes).unwrap_or_default(),
+            FontDecoder::Unknown => String::new(),
+        }
+    }
+
+    fn decode(&self, glyphs: &str) -> String {
+        match self {
+            FontDecoder::Legacy(conversion) => conversion.encoding.decode_low_byte(glyphs),
+            FontDecoder::LegacyMapped(conversion, _) => conversion.encoding.decode_auto(glyphs),
+            _ => glyphs.to_string(),
+        }
+    }
+}
+
+//Text of the pages, line by line and paragraph by paragraph
+#[derive(Default)]
+struct TextBuilder {
+    paragraphs: Vec<String>,
+    line: String,
+    run: String, //legacy glyphs not decoded yet: a syllable may be split between text operators
+    run_font: Option<Vec<u8>>,
+}
+
+impl TextBuilder {
+    fn flush_run(&mut self, decoders: &BTreeMap<Vec<u8>, FontDecoder>) {
+        if let Some(decoder) = self.run_font.take().and_then(|font| decoders.get(&font)) {
+            self.line.push_str(&decoder.decode(&self.run));
+        }
+        self.run.clear();
+    }
+
+    fn push(&mut self, font: &[u8], decoders: &BTreeMap<Vec<u8>, FontDecoder>, codes: &[u8]) {
+        let decoder = match decoders.get(font) {
+            Some(decoder) => decoder,
+            None => return,
+        };
+        if self.run_font.as_deref() != Some(font) {
+            self.flush_run(decoders);
+        }
+        if decoder.is_legacy() {
+            self.run_font = Some(font.to_vec());
+            self.run.push_str(&decoder.read(codes));
+        } else {
+            self.line.push_str(&decoder.read(codes));
+        }
+    }
+
+    fn space(&mut self, decoders: &BTreeMap<Vec<u8>, FontDecoder>) {
+        self.flush_run(decoders);
+        if !self.line.is_empty() && !self.line.ends_with(' ') {
+            self.line.push(' ');
+        }
+    }
+
+    //Lines of a paragraph are joined with a space
+    fn end_line(&mut self, decoders: &BTreeMap<Vec<u8>, FontDecoder>) {
+        self.space(decoders);
+    }
+
+    fn end_paragraph(&mut self, decoders: &BTreeMap<Vec<u8>, FontDecoder>) {
+        self.flush_run(decoders);
+        let paragraph = self.line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !paragraph.is_empty() {
+            self.paragraphs.push(paragraph);
+        }
+        self.line.clear();
+    }
+}
+
+fn number(object: &Object) -> f32 {
+    object.as_float().unwrap_or(0.0)
+}
+
+//Adds the text of a page. Lines are told by the moves of the text position; a gap of more
+//than about a line and a half between lines, or a move up, starts a new paragraph.
+fn page_text(document: &Document, page: lopdf::ObjectId, conversions: &[FontConversion], text: &mut TextBuilder) -> Result<(), String> {
+    let fonts = document.get_page_fonts(page).map_err(|e| e.to_string())?;
+    let decoders: BTreeMap<Vec<u8>, FontDecoder> = fonts.iter()
+        .map(|(name, font)| (name.clone(), font_decoder(font, document, conversions)))
+        .collect();
+    let content = Content::decode(&document.get_page_content(page)).map_err(|e| e.to_string())?;
+
+    let mut font: Vec<u8> = Vec::new();
+    let mut size = 12.0;
+    let mut scale = 1.0; //vertical scale of the text matrix
+    let mut leading = 0.0;
+    let mut y = 0.0;
+    let mut last_y: Option<f32> = None;
+    for operation in &content.operations {
+        let operands = &operation.operands;
+        match operation.operator.as_str() {
+            "Tf" => {
+                font = operands.first().and_then(|o| o.as_name().ok()).unwrap_or_default().to_vec();
+                size = operands.get(1).map_or(size, number);
+            }
+            "TL" => leading = operands.first().map_or(0.0, number),
+            "Tm" => {
+                scale = operands.get(3).map_or(1.0, number);
+                y = operands.get(5).map_or(0.0, number);
+            }
+            "Td" | "TD" => {
+                let ty = operands.get(1).map_or(0.0, number);
+                if operation.operator == "TD" {
+                    leading = -ty;
+                }
+                y += ty * scale;
+            }
+            "T*" | "'" | "\"" => y -= leading * scale,
+            "BT" => {
+                scale = 1.0;
+                y = 0.0;
+            }
+            _ => (),
+        }
+        let strings: Vec<&[u8]> = match operation.operator.as_str() {
+            "Tj" | "'" => operands.iter().filter_map(|o| o.as_str().ok()).collect(),
+            "\"" => operands.get(2).and_then(|o| o.as_str().ok()).into_iter().collect(),
+            "TJ" => Vec::new(),
+            _ => continue,
+        };
+        if let Some(last) = last_y {
+            let gap = last - y;
+            if gap < -0.1 || gap > 1.6 * size * scale.abs() {
+                text.end_paragraph(&decoders);
+            } else if gap > 0.1 {
+                text.end_line(&decoders);
+            }
+        }
+        last_y = Some(y);
+        for codes in strings {
+            text.push(&font, &decoders, codes);
+        }
+        if operation.operator == "TJ" {
+            for item in operands.first().and_then(|o| o.as_array().ok()).into_iter().flatten() {
+                match item {
+                    Object::String(codes, _) => text.push(&font, &decoders, codes),
+                    //a large negative adjustment is a space drawn without a space character
+                    _ if number(item) < -250.0 => text.space(&decoders),
+                    _ => (),
+                }
+            }
+        }
+    }
+    text.end_paragraph(&decoders);
+    Ok(())
+}
+
+//Unicode text of a PDF, paragraphs separated by a blank line. Text shown in a legacy font of
+//one of the conversions, told by the font's base name (or, for an unnamed font, by a ToUnicode
+//map into the U+F0XX glyphs), is decoded a run at a time, so that glyphs drawn in visual order
+//(vowel signs before their consonant) come back in logical order. Text in other fonts is read
+//through its encoding or ToUnicode map.
+pub fn extract_pdf_text(input: &[u8], conversions: &[FontConversion]) -> Result<String, String> {
+    let document = Document::load_mem(input).map_err(|e| e.to_string())?;
+    let mut text = TextBuilder::default();
+    for page in document.get_pages().values() {
+        page_text(&document, *page, conversions, &mut text)?;
+    }
+    let mut output = text.paragraphs.join("\n\n");
+    output.push('\n');
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::fonts::Direction;
+    use lopdf::{dictionary, Stream};
+
+    //One page with a line in STMZH split across two text operators, and two in Helvetica
+    fn sample_pdf() -> Vec<u8> {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let stmzh = document.add_object(dictionary! {
+            "Type" => "Font", "Subtype" => "TrueType", "BaseFont" => "ABCDEF+STMZH",
+        });
+        let helvetica = document.add_object(dictionary! {
+            "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Helvetica", "Encoding" => "WinAnsiEncoding",
+        });
+        let resources = document.add_object(dictionary! {
+            "Font" => dictionary! { "F1" => stmzh, "F2" => helvetica },
+        });
+        let content = b"BT /F1 12 Tf 72 700 Td (\xd8) Tj [(\xef) -20 (V)] TJ /F2 12 Tf ( A) Tj 0 -14 Td (B) Tj 0 -40 Td (C) Tj ET".to_vec();
+        let content = document.add_object(Stream::new(dictionary! {}, content));
+        let page = document.add_object(dictionary! {
+            "Type" => "Page", "Parent" => pages_id, "Contents" => content, "Resources" => resources,
+        });
+        document.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages", "Kids" => vec![page.into()], "Count" => 1,
+        }));
+        let catalog = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        document.trailer.set("Root", catalog);
+        let mut output = Vec::new();
+        document.save_to(&mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn decodes_legacy_fonts() {
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        assert_eq!(extract_pdf_text(&sample_pdf(), &[conversion]).unwrap(), "கொ A B\n\nC\n");
+    }
+}
@@ -0,0 +1,89 @@
+use crate::normalize::compose_pair;
+use crate::{parse_consonant_helper, parse_ksha, parse_mark, MarkType, CONSONANTS};
+use std::fmt;
+
+//A vowel sign typed before its consonant (ெக), moved after it (கெ)
+#[derive(Debug, PartialEq)]
+pub struct VisualOrderFix {
+    pub offset: usize, //byte offset in the original text
+    pub original: String,
+    pub repaired: String,
+}
+
+impl fmt::Display for VisualOrderFix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\t{} -> {}", self.offset, self.original, self.repaired)
+    }
+}
+
+//ெ ே ை, or ொ ோ ௌ typed as one key
+fn prefix_sign(i: &str) -> Option<(char, &str)> {
+    match parse_mark(i) {
+        Ok((rest, (MarkType::Preceding, sign))) | Ok((rest, (MarkType::PrecedingAndFollowing, sign))) => Some((sign, rest)),
+        _ => None,
+    }
+}
+
+//க, or க்ஷ
+fn consonant(i: &str) -> Option<(&str, &str)> {
+    if let Ok((rest, ksha)) = parse_ksha(i) {
+        return Some((ksha, rest));
+    }
+    parse_consonant_helper(i).ok().map(|(rest, c)| (&i[..c.len_utf8()], rest))
+}
+
+//Length of the visual-order syllable i starts with and its logical order: ெக -> கெ,
+//and the two-part signs, ெகா -> கொ, ேகா -> கோ, ெகௗ -> கௌ. None if the consonant
+//carries a sign of its own (ெகி), since the typist's intent isn't known. A prefix sign
+//after the consonant is the next syllable's (ைகைக).
+fn repair_syllable(i: &str) -> Option<(usize, String)> {
+    let (sign, rest) = prefix_sign(i)?;
+    let (consonant, rest) = consonant(rest)?;
+    match rest.chars().next() {
+        Some(second) => match compose_pair(sign, second) {
+            Some(composed) => Some((i.len() - rest.len() + second.len_utf8(), format!("{}{}", consonant, composed))),
+            None if parse_mark(rest).is_ok() && prefix_sign(rest).is_none() => None,
+            None => Some((i.len() - rest.len(), format!("{}{}", consonant, sign))),
+        },
+        None => Some((i.len() - rest.len(), format!("{}{}", consonant, sign))),
+    }
+}
+
+//Moves the prefix vowel signs that were typed before their consonant, as on legacy font
+//keyboards, after it. A sign is only moved when nothing before it can carry it.
+//Returns the repaired text and every fix applied.
+pub fn repair_visual_order(text: &str) -> (String, Vec<VisualOrderFix>) {
+    let mut output = String::with_capacity(text.len());
+    let mut fixes = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let carried = output.chars().next_back().is_some_and(|last| CONSONANTS.contains(last));
+        if let Some((length, repaired)) = repair_syllable(rest).filter(|_| !carried) {
+            fixes.push(VisualOrderFix {
+                offset: text.len() - rest.len(),
+                original: rest[..length].to_string(),
+                repaired: repaired.clone(),
+            });
+            output.push_str(&repaired);
+            rest = &rest[length..];
+        } else {
+            output.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    (output, fixes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repairs_visual_order() {
+        let (repaired, fixes) = repair_visual_order("\u{0bc6}காழும்பு \u{0bc7}க \u{0bc8}க\u{0bc8}க \u{0bca}க \u{0bc6}க்ஷ \u{0bc6}க\u{0bd7} கெ \u{0bc6}கி");
+        assert_eq!(repaired, "கொழும்பு கே கைகை கொ க்ஷெ கௌ கெ \u{0bc6}கி");
+        assert_eq!(fixes.len(), 7);
+        assert_eq!(fixes[0], VisualOrderFix { offset: 0, original: "\u{0bc6}கா".to_string(), repaired: "கொ".to_string() });
+        assert_eq!(fixes[3].offset, "\u{0bc6}காழும்பு \u{0bc7}க \u{0bc8}க".len());
+    }
+}
@@ -0,0 +1,309 @@
+use crate::fonts::{Direction, FontConversion};
+use crate::low_byte::{cp1252_byte, cp1252_char, low_byte_to_pua, pua_to_low_byte};
+use std::collections::HashMap;
+
+//Destinations whose text is no document text
+const SKIPPED_DESTINATIONS: &[&[u8]] = &[
+    b"colortbl", b"stylesheet", b"info", b"pict", b"object", b"fldinst", b"listtable",
+    b"listoverridetable", b"revtbl", b"rsidtbl", b"generator", b"themedata",
+    b"colorschememapping", b"datastore", b"latentstyles", b"xmlnstbl", b"filetbl",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    GroupStart,
+    GroupEnd,
+    Control(&'a [u8], Option<i32>), //control word and its parameter
+    Hex(u8), //\'xx
+    Symbol(u8), //control symbol: \\, \{, \~, \*...
+    Text(u8),
+}
+
+//Next token of input, with the bytes it takes (including the space ending a control word)
+fn next_token(input: &[u8]) -> (Token<'_>, usize) {
+    match input[0] {
+        b'{' => (Token::GroupStart, 1),
+        b'}' => (Token::GroupEnd, 1),
+        b'\\' if input.len() > 1 && input[1].is_ascii_alphabetic() => {
+            let word_end = 1 + input[1..].iter().take_while(|b| b.is_ascii_alphabetic()).count();
+            let mut end = word_end;
+            if end < input.len() && input[end] == b'-' {
+                end += 1;
+            }
+            end += input[end..].iter().take_while(|b| b.is_ascii_digit()).count();
+            let param = std::str::from_utf8(&input[word_end..end]).ok().and_then(|p| p.parse().ok());
+            if end < input.len() && input[end] == b' ' {
+                end += 1;
+            }
+            (Token::Control(&input[1..word_end], param), end)
+        }
+        b'\\' if input.len() > 3 && input[1] == b'\'' => {
+            let hex = std::str::from_utf8(&input[2..4]).ok().and_then(|h| u8::from_str_radix(h, 16).ok());
+            match hex {
+                Some(byte) => (Token::Hex(byte), 4),
+                None => (Token::Symbol(b'\''), 2),
+            }
+        }
+        b'\\' if input.len() > 1 => (Token::Symbol(input[1]), 2),
+        byte => (Token::Text(byte), 1),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Destination {
+    Text,
+    FontTable,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Group {
+    destination: Destination,
+    font: Option<i32>,
+    uc: usize, //number of fallback characters after \uN
+    first: bool, //no token read yet in the group
+}
+
+//Entry of \fonttbl being read: its tokens are kept until its name is known
+#[derive(Debug, Default)]
+struct FontEntry {
+    number: i32,
+    depth: usize, //groups open when it started
+    raw: Vec<u8>, //tokens of the entry, but its name and \fcharsetN
+    charset: Option<(usize, Vec<u8>)>, //where \fcharsetN was in raw, and its bytes
+    name: Vec<u8>,
+}
+
+struct RtfConverter<'c> {
+    conversion: &'c FontConversion,
+    output: Vec<u8>,
+    source_fonts: HashMap<i32, bool>,
+    default_font: Option<i32>,
+    run: String, //text of the source font not converted yet, as U+F0XX (decode) or Unicode (encode)
+    run_uc: usize, //fallback characters still to skip after \uN
+    entry: Option<FontEntry>,
+}
+
+impl RtfConverter<'_> {
+    fn is_source(&self, group: &Group) -> bool {
+        group.destination == Destination::Text
+            && group.font.or(self.default_font).and_then(|f| self.source_fonts.get(&f)).copied().unwrap_or(false)
+    }
+
+    fn push_unicode(&mut self, c: char) {
+        match c {
+            '\\' | '{' | '}' => {
+                self.output.push(b'\\');
+                self.output.push(c as u8);
+            }
+            '\t' => self.output.extend_from_slice(b"\\tab "),
+            c if c.is_ascii() && !c.is_ascii_control() => self.output.push(c as u8),
+            c => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    self.output.extend_from_slice(format!("\\u{}?", *unit as i16).as_bytes());
+                }
+            }
+        }
+    }
+
+    fn push_legacy(&mut self, c: char) {
+        match cp1252_byte(pua_to_low_byte(&c.to_string()).chars().next().unwrap()) {
+            Some(b) if b.is_ascii() && !b.is_ascii_control() && !b"\\{}".contains(&b) => self.output.push(b),
+            Some(b) => self.output.extend_from_slice(format!("\\'{:02x}", b).as_bytes()),
+            None => self.push_unicode(c), //no glyph for it in the encoding
+        }
+    }
+
+    //Converts the pending run of text
+    fn flush(&mut self, uc: usize) {
+        if self.run.is_empty() {
+            return;
+        }
+        let run = std::mem::take(&mut self.run);
+        let converted = self.conversion.convert(&run);
+        let wrap = self.conversion.direction == Direction::Decode && uc != 1;
+        if wrap {
+            self.output.extend_from_slice(b"{\\uc1 ");
+        }
+        for c in converted.chars() {
+            match self.conversion.direction {
+                Direction::Decode => self.push_unicode(c),
+                Direction::Encode => self.push_legacy(c),
+            }
+        }
+        if wrap {
+            self.output.push(b'}');
+        }
+    }
+
+    //Text of a source font: bytes are glyphs of the legacy font when decoding,
+    //Windows-1252 characters when encoding
+    fn push_run(&mut self, c: char) {
+        if self.run_uc > 0 {
+            self.run_uc -= 1;
+            return;
+        }
+        match self.conversion.direction {
+            Direction::Decode => self.run.push_str(&low_byte_to_pua(&c.to_string())),
+            Direction::Encode => self.run.push(c),
+        }
+    }
+
+    //Bytes of a token kept as is
+    fn emit(&mut self, raw: &[u8]) {
+        match self.entry.as_mut() {
+            Some(entry) => entry.raw.extend_from_slice(raw),
+            None => self.output.extend_from_slice(raw),
+        }
+    }
+
+    fn finish_entry(&mut self) {
+        let mut entry = match self.entry.take() {
+            Some(entry) => entry,
+            None => return,
+        };
+        let name = String::from_utf8_lossy(&entry.name).trim().to_string();
+        let source = self.conversion.is_source_font(&name);
+        self.source_fonts.insert(entry.number, source);
+        if let Some((at, charset)) = entry.charset {
+            let charset: &[u8] = match self.conversion.direction {
+                _ if !source => &charset,
+                Direction::Encode if !self.conversion.low_byte => b"\\fcharset2 ", //symbol font
+                _ => b"\\fcharset0 ",
+            };
+            entry.raw.splice(at..at, charset.iter().copied());
+        }
+        self.output.extend_from_slice(&entry.raw);
+        if source {
+            self.output.extend_from_slice(self.conversion.target_font().as_bytes());
+        } else {
+            self.output.extend_from_slice(&entry.name);
+        }
+    }
+}
+
+//Converts the text set in a source font, and renames the font in \fonttbl.
+//Decoding writes the text as \uN; encoding writes the bytes of the legacy font as \'xx.
+pub fn convert_rtf(input: &[u8], conversion: &FontConversion) -> Result<Vec<u8>, String> {
+    if !input.starts_with(b"{\\rtf") {
+        return Err(String::from("not an RTF file"));
+    }
+    let conversion = &conversion.for_document(&String::from_utf8_lossy(input));
+    let mut converter = RtfConverter {
+        conversion,
+        output: Vec::with_capacity(input.len()),
+        source_fonts: HashMap::new(),
+        default_font: None,
+        run: String::new(),
+        run_uc: 0,
+        entry: None,
+    };
+    let mut groups = vec![Group { destination: Destination::Text, font: None, uc: 1, first: false }];
+    let mut pos = 0;
+    while pos < input.len() {
+        let (token, len) = next_token(&input[pos..]);
+        let raw = &input[pos..pos + len];
+        pos += len;
+        let group = groups.last_mut().ok_or_else(|| format!("unbalanced group at byte {}", pos))?;
+        let first = std::mem::replace(&mut group.first, false);
+        let source = converter.is_source(group);
+
+        match token {
+            Token::GroupStart => {
+                converter.flush(group.uc);
+                let group = Group { first: true, ..*group };
+                groups.push(group);
+                converter.emit(raw);
+            }
+            Token::GroupEnd => {
+                converter.flush(group.uc);
+                groups.pop();
+                if converter.entry.as_ref().is_some_and(|e| e.depth > groups.len()) {
+                    converter.finish_entry();
+                }
+                converter.emit(raw);
+            }
+            Token::Symbol(b'*') if first => {
+                group.destination = Destination::Skipped;
+                converter.emit(raw);
+            }
+            Token::Control(word, param) => {
+                if first && word == b"fonttbl" {
+                    group.destination = Destination::FontTable;
+                } else if first && SKIPPED_DESTINATIONS.contains(&word) {
+                    group.destination = Destination::Skipped;
+                }
+                match (word, param) {
+                    (b"u", Some(n)) if source => {
+                        let c = std::char::from_u32(if n < 0 { n + 0x10000 } else { n } as u32).unwrap_or('?');
+                        converter.run.push(c);
+                        converter.run_uc = group.uc;
+                        continue;
+                    }
+                    (b"bin", Some(n)) => { //binary data, kept as is
+                        converter.flush(group.uc);
+                        let end = (pos + n.max(0) as usize).min(input.len());
+                        converter.emit(raw);
+                        converter.emit(&input[pos..end]);
+                        pos = end;
+                        continue;
+                    }
+                    (b"f", Some(n)) if group.destination == Destination::FontTable => {
+                        converter.finish_entry();
+                        converter.entry = Some(FontEntry { number: n, depth: groups.len(), ..Default::default() });
+                    }
+                    (b"fcharset", _) if converter.entry.is_some() => {
+                        let entry = converter.entry.as_mut().unwrap();
+                        entry.charset = Some((entry.raw.len(), raw.to_vec()));
+                        continue;
+                    }
+                    (b"f", Some(n)) => {
+                        converter.flush(group.uc);
+                        group.font = Some(n);
+                    }
+                    (b"deff", Some(n)) => converter.default_font = Some(n),
+                    (b"uc", Some(n)) => group.uc = n.max(0) as usize,
+                    _ => converter.flush(group.uc),
+                }
+                converter.emit(raw);
+            }
+            Token::Hex(byte) if source => converter.push_run(cp1252_char(byte)),
+            Token::Symbol(c @ (b'\\' | b'{' | b'}')) if source => converter.push_run(c as char),
+            Token::Text(b'\r') | Token::Text(b'\n') if !converter.run.is_empty() => (), //ignored by readers
+            Token::Text(byte) if source => converter.push_run(cp1252_char(byte)),
+            Token::Text(b';') if converter.entry.is_some() && group.destination == Destination::FontTable => {
+                converter.finish_entry();
+                converter.emit(raw);
+            }
+            Token::Text(byte) if converter.entry.is_some() && group.destination == Destination::FontTable => {
+                converter.entry.as_mut().unwrap().name.push(byte);
+            }
+            _ => {
+                converter.flush(group.uc);
+                converter.emit(raw);
+            }
+        }
+    }
+    converter.finish_entry();
+    Ok(converter.output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+
+    const RTF: &[u8] = b"{\\rtf1\\ansi\\deff1{\\fonttbl{\\f0\\fnil\\fcharset2 STMZH;}{\\f1\\froman\\fcharset0 Times New Roman;}}\r\n{\\colortbl;\\red0\\green0\\blue0;}\r\n\\pard\\f0\\fs24 \\'d8\\'efV \\'5c\\f1  and {\\b bold}\\par\r\n}";
+
+    #[test]
+    fn decodes_legacy_font_runs() {
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        let rtf = convert_rtf(RTF, &conversion).unwrap();
+        assert_eq!(String::from_utf8(rtf.clone()).unwrap(), "{\\rtf1\\ansi\\deff1{\\fonttbl{\\f0\\fnil\\fcharset0 Latha;}{\\f1\\froman\\fcharset0 Times New Roman;}}\r\n{\\colortbl;\\red0\\green0\\blue0;}\r\n\\pard\\f0\\fs24 \\u2965?\\u3018? \\u2990?\\f1  and {\\b bold}\\par\r\n}");
+
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Encode);
+        let back = convert_rtf(&rtf, &conversion).unwrap();
+        assert_eq!(String::from_utf8(back).unwrap(), "{\\rtf1\\ansi\\deff1{\\fonttbl{\\f0\\fnil\\fcharset2 STMZH;}{\\f1\\froman\\fcharset0 Times New Roman;}}\r\n{\\colortbl;\\red0\\green0\\blue0;}\r\n\\pard\\f0\\fs24 \\'d8\\'efV \\'5c\\f1  and {\\b bold}\\par\r\n}");
+    }
+}
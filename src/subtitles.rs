@@ -0,0 +1,96 @@
+use crate::fonts::FontConversion;
+use crate::low_byte::bytes_to_low_byte;
+
+//Cue text tags of SRT and WebVTT (<i>, </font>, <c.yellow>, <v Speaker>...)
+const TAGS: [&str; 10] = ["b", "i", "u", "s", "font", "c", "v", "lang", "ruby", "rt"];
+
+//Length of the styling tag at the beginning of text: a cue tag, a WebVTT timestamp
+//(<00:01.500>) or an SRT override block ({\an8})
+fn tag_length(text: &str) -> Option<usize> {
+    if text.starts_with("{\\") {
+        return text.find('}').map(|end| end + 1);
+    }
+    let inner = text.strip_prefix('<')?;
+    let end = inner.find('>')?;
+    let name = inner[..end].trim_start_matches('/');
+    let name = name.split(['.', ' ']).next().unwrap_or_default().to_ascii_lowercase();
+    let is_timestamp = !name.is_empty() && name.chars().all(|c| c.is_ascii_digit() || c == ':' || c == '.');
+    if TAGS.contains(&name.as_str()) || is_timestamp {
+        Some(end + 2)
+    } else {
+        None
+    }
+}
+
+//A line of cue text, converted between its tags
+fn convert_cue_line(line: &str, convert: &dyn Fn(&str) -> String, output: &mut String) {
+    let mut text_start = 0;
+    let mut i = 0;
+    while i < line.len() {
+        match tag_length(&line[i..]) {
+            Some(length) => {
+                output.push_str(&convert(&line[text_start..i]));
+                output.push_str(&line[i..i + length]);
+                i += length;
+                text_start = i;
+            }
+            None => i += line[i..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    output.push_str(&convert(&line[text_start..]));
+}
+
+//Converts the text of the cues of an SRT or WebVTT file. Cue numbers and identifiers, timings,
+//styling tags, line breaks and the blocks without timing (WEBVTT header, NOTE, STYLE, REGION)
+//are kept as they are. 8-bit input is read as Windows-1252 and written as UTF-8.
+pub fn convert_subtitles(input: &[u8], conversion: &FontConversion) -> Result<Vec<u8>, String> {
+    let text = match std::str::from_utf8(input) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes_to_low_byte(input),
+    };
+    let convert = conversion.document_converter(&text);
+    let mut output = String::with_capacity(text.len());
+    let mut in_cue = false; //after the timing line of a block
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[content.len()..];
+        if content.trim().is_empty() {
+            in_cue = false;
+            output.push_str(line);
+        } else if in_cue {
+            convert_cue_line(content, &convert, &mut output);
+            output.push_str(ending);
+        } else {
+            in_cue = content.contains("-->") && !content.starts_with("NOTE");
+            output.push_str(line);
+        }
+    }
+    Ok(output.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::fonts::Direction;
+
+    #[test]
+    fn converts_cue_text_only() {
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        let srt = "1\r\n00:00:01,000 --> 00:00:02,500\r\n<i>\u{f0d8}\u{f0ef}\u{f056}</i>\r\n{\\an8}\u{f0ef}\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,000\r\n\u{f0ef}\r\n";
+        let converted = String::from_utf8(convert_subtitles(srt.as_bytes(), &conversion).unwrap()).unwrap();
+        assert_eq!(converted, "1\r\n00:00:01,000 --> 00:00:02,500\r\n<i>கொ</i>\r\n{\\an8}க\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,000\r\nக\r\n");
+
+        let vtt = "WEBVTT\n\nNOTE \u{f0ef}\n\nintro\n00:01.000 --> 00:02.000 line:0\n<v Kumar>\u{f0ef}<00:01.500><c.yellow>\u{f0ef}</c>\n";
+        let converted = String::from_utf8(convert_subtitles(vtt.as_bytes(), &conversion).unwrap()).unwrap();
+        assert_eq!(converted, "WEBVTT\n\nNOTE \u{f0ef}\n\nintro\n00:01.000 --> 00:02.000 line:0\n<v Kumar>க<00:01.500><c.yellow>க</c>\n");
+    }
+
+    #[test]
+    fn keeps_latin_cues_of_pua_files() {
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        let srt = "1\n00:00:01,000 --> 00:00:02,000\n\u{f0ef}\n\n2\n00:00:03,000 --> 00:00:04,000\n<i>Hello World</i>\n";
+        let converted = String::from_utf8(convert_subtitles(srt.as_bytes(), &conversion).unwrap()).unwrap();
+        assert_eq!(converted, "1\n00:00:01,000 --> 00:00:02,000\nக\n\n2\n00:00:03,000 --> 00:00:04,000\n<i>Hello World</i>\n");
+    }
+}
@@ -0,0 +1,83 @@
+//Tamil Supplement block (U+11FC0-U+11FFF): the traditional fractions, and the signs of
+//measures, money and accounts
+
+pub const FRACTIONS: std::ops::RangeInclusive<char> = '\u{11fc0}'..='\u{11fd4}';
+pub const SIGNS: std::ops::RangeInclusive<char> = '\u{11fd5}'..='\u{11ff1}';
+pub const END_OF_TEXT: char = '\u{11fff}';
+
+//Fractions as ASCII (kiizh, the 1/320 downscaling factor, has none), and the signs of
+//measures and money spelt out. Signs of accounts have no decomposition.
+const DECOMPOSITIONS: [(char, &str); 35] = [
+    ('\u{11fc0}', "1/320"),
+    ('\u{11fc1}', "1/160"),
+    ('\u{11fc2}', "1/80"),
+    ('\u{11fc3}', "1/64"),
+    ('\u{11fc4}', "1/40"),
+    ('\u{11fc5}', "1/32"),
+    ('\u{11fc6}', "3/80"),
+    ('\u{11fc7}', "3/64"),
+    ('\u{11fc8}', "1/20"),
+    ('\u{11fc9}', "1/16"),
+    ('\u{11fca}', "1/16"),
+    ('\u{11fcb}', "1/10"),
+    ('\u{11fcc}', "1/8"),
+    ('\u{11fcd}', "3/20"),
+    ('\u{11fce}', "3/16"),
+    ('\u{11fcf}', "1/5"),
+    ('\u{11fd0}', "1/4"),
+    ('\u{11fd1}', "1/2"),
+    ('\u{11fd2}', "1/2"),
+    ('\u{11fd3}', "3/4"),
+    ('\u{11fd5}', "நெல்"),
+    ('\u{11fd6}', "செவிடு"),
+    ('\u{11fd7}', "ஆழாக்கு"),
+    ('\u{11fd8}', "உழக்கு"),
+    ('\u{11fd9}', "மூவுழக்கு"),
+    ('\u{11fda}', "குறுணி"),
+    ('\u{11fdb}', "பதக்கு"),
+    ('\u{11fdc}', "முக்குறுணி"),
+    ('\u{11fdd}', "காசு"),
+    ('\u{11fde}', "பணம்"),
+    ('\u{11fdf}', "பொன்"),
+    ('\u{11fe0}', "வராகன்"),
+    ('\u{11fe1}', "பாரம்"),
+    ('\u{11fe2}', "குழி"),
+    ('\u{11fe3}', "வேலி"),
+];
+
+pub fn is_fraction(c: char) -> bool {
+    FRACTIONS.contains(&c)
+}
+
+pub fn is_sign(c: char) -> bool {
+    SIGNS.contains(&c) || c == END_OF_TEXT
+}
+
+//What a font without glyphs for the block is given instead
+pub fn decomposition(c: char) -> Option<&'static str> {
+    DECOMPOSITIONS.iter().find(|(s, _)| *s == c).map(|(_, d)| *d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::{convert_stmzh_unic, convert_unic_stmzh, parse_entity};
+
+    #[test]
+    fn converts_fractions_and_signs() {
+        let (_, entity) = parse_entity("\u{11fd0}").unwrap();
+        assert_eq!(entity.kind(), "Fraction");
+        assert_eq!(parse_entity("\u{11fed}").unwrap().1.kind(), "Sign");
+        assert_eq!(decomposition('\u{11fd4}'), None);
+        assert_eq!(convert_unic_stmzh("\u{11fd0} \u{11fde} \u{11fed}"), convert_unic_stmzh("1/4 பணம் \u{11fed}"));
+
+        let encoding = Encoding::from_json_str("{\"name\": \"Old\", \"symbols\": {\"\u{11fd0}\": \"q\"}}").unwrap();
+        assert_eq!(encoding.encode("\u{11fd0}\u{11fd1}"), "q1/2");
+        assert_eq!(encoding.decode("q"), "\u{11fd0}");
+        assert_eq!(encoding.encode("௨\u{11fd1}"), "2 1/2");
+
+        let two_and_a_quarter = convert_unic_stmzh("௨\u{11fd0} 1\u{11fd0}\u{11fd1}");
+        assert_eq!(convert_stmzh_unic(&two_and_a_quarter), "2 1/4 1 1/4 1/2");
+    }
+}
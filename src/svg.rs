@@ -0,0 +1,105 @@
+use crate::css::{font_families, font_family, rewrite_font_families, rewrite_font_family_value, rewrite_style_sheet, FontRules};
+use crate::fonts::FontConversion;
+use crate::xml::{attribute, convert_text, rewrite_attributes, rewrite_xml};
+use quick_xml::events::{BytesCData, BytesStart, Event};
+
+//Elements whose text is drawn
+const TEXT_ELEMENTS: [&[u8]; 3] = [b"text", b"tspan", b"textPath"];
+
+//"style", "svg:style" -> "style"
+fn local_name(name: &[u8]) -> &[u8] {
+    name.iter().position(|b| *b == b':').map_or(name, |p| &name[p + 1..])
+}
+
+fn is_style_element(name: &[u8]) -> bool {
+    local_name(name) == b"style"
+}
+
+fn is_text_element(name: &[u8]) -> bool {
+    TEXT_ELEMENTS.contains(&local_name(name))
+}
+
+//Some(is it a source font) if the element sets a font, by its style, font-family or class
+fn element_font(e: &BytesStart, rules: &FontRules, conversion: &FontConversion) -> Result<Option<bool>, String> {
+    let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+    let family = match attribute(e, b"style")?.as_deref().and_then(font_family) {
+        Some(family) => Some(family),
+        None => match attribute(e, b"font-family")? {
+            Some(family) => Some(family),
+            None => rules.font_family(&name, attribute(e, b"id")?.as_deref(), attribute(e, b"class")?.as_deref()).cloned(),
+        },
+    };
+    Ok(family.map(|family| font_families(&family).first().is_some_and(|f| conversion.is_source_font(f))))
+}
+
+//font-family attributes and declarations of style attributes naming a source font,
+//changed to the target font
+fn rewrite_font_references<'a>(e: BytesStart<'a>, conversion: &FontConversion) -> Result<BytesStart<'a>, String> {
+    let rewritten = rewrite_attributes(&e, |key, value| match key {
+        b"font-family" => rewrite_font_family_value(value, conversion),
+        b"style" => Some(rewrite_font_families(value, conversion)).filter(|v| v != value),
+        _ => None,
+    })?;
+    Ok(rewritten.unwrap_or(e))
+}
+
+//Converts the text of the <text>, <tspan> and <textPath> elements whose font (by their
+//font-family or style attribute, the rules of the <style> elements, or inherited from their
+//parents) is a source font. Font references are rewritten to the target font.
+pub fn convert_svg(input: &[u8], conversion: &FontConversion) -> Result<Vec<u8>, String> {
+    let svg = std::str::from_utf8(input).map_err(|e| e.to_string())?;
+    let conversion = &conversion.for_document(svg);
+    let mut rules = FontRules::default();
+    let mut fonts: Vec<Option<bool>> = Vec::new(); //for each open element: the font it sets
+    let mut in_style = false;
+    let svg = rewrite_xml(svg, |event, path| {
+        Ok(match event {
+            Event::Start(e) => {
+                in_style = is_style_element(e.name().as_ref());
+                fonts.push(element_font(&e, &rules, conversion)?);
+                Event::Start(rewrite_font_references(e, conversion)?)
+            }
+            Event::Empty(e) => Event::Empty(rewrite_font_references(e, conversion)?),
+            Event::End(e) => {
+                in_style = false;
+                fonts.pop();
+                Event::End(e)
+            }
+            Event::Text(e) if in_style => {
+                let css = e.unescape().map_err(|e| e.to_string())?;
+                rules.add_style_sheet(&css);
+                convert_text(&e, |css| rewrite_style_sheet(css, conversion))?
+            }
+            Event::CData(e) if in_style => {
+                let css = String::from_utf8_lossy(&e).into_owned();
+                rules.add_style_sheet(&css);
+                Event::CData(BytesCData::new(rewrite_style_sheet(&css, conversion)).into_owned())
+            }
+            Event::Text(e) => {
+                let font = fonts.iter().rev().find_map(|f| *f);
+                if font == Some(true) && path.last().is_some_and(|name| is_text_element(name)) {
+                    convert_text(&e, |text| conversion.convert(text))?
+                } else {
+                    Event::Text(e)
+                }
+            }
+            event => event,
+        })
+    });
+    svg.map(String::into_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::fonts::Direction;
+
+    #[test]
+    fn converts_text_in_legacy_fonts() {
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg"><style>.t { font-family: STMZH }</style><g font-family="STMZH, serif"><text x="0">&#xF0D8;&#xF0EF;&#xF056;<tspan style="font-family: Arial">A</tspan></text></g><text class="t">&#xF0EF;</text><text>&#xF0EF;</text></svg>"#;
+        let converted = String::from_utf8(convert_svg(svg.as_bytes(), &conversion).unwrap()).unwrap();
+        assert_eq!(converted, r#"<svg xmlns="http://www.w3.org/2000/svg"><style>.t { font-family: Latha }</style><g font-family="Latha, serif"><text x="0">கொ<tspan style="font-family: Arial">A</tspan></text></g><text class="t">க</text><text>&#xF0EF;</text></svg>"#);
+    }
+}
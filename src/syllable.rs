@@ -0,0 +1,233 @@
+use crate::{parse_entity, TamilDetailedEntity, VOWELS, VOWEL_SIGNS};
+use serde::Serialize;
+use std::fmt;
+
+const PULLI: char = '\u{0bcd}';
+
+//From the parser's entities: Vowel is Vowel or Aytham, SeparateEntity with pulli is
+//Consonant, and Consonant, the other SeparateEntity and ComposedEntity are ConsonantVowel
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum SyllableKind {
+    Vowel, //உயிர்
+    Consonant, //மெய்: with pulli
+    ConsonantVowel, //உயிர்மெய்: a consonant with its inherent அ or a vowel sign
+    Aytham, //ஃ
+    Other, //anything else: digits, signs, lone marks, other scripts
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum ConsonantClass {
+    Vallinam, //வல்லினம்: கசடதபற
+    Mellinam, //மெல்லினம்: ஙஞணநமன
+    Idaiyinam, //இடையினம்: யரலவழள
+    Grantha, //ஜஷஸஹஶ, க்ஷ, ஸ்ரீ
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum VowelLength {
+    Kuril, //குறில்: அஇஉஎஒ
+    Nedil, //நெடில்: ஆஈஊஏஐஓஔ
+}
+
+pub const VALLINAM: &str = "கசடதபற";
+pub const MELLINAM: &str = "ஙஞணநமன";
+pub const IDAIYINAM: &str = "யரலவழள";
+pub const GRANTHA: &str = "ஜஷஸஹஶ";
+pub const KURIL: &str = "அஇஉஎஒ";
+pub const NEDIL: &str = "ஆஈஊஏஐஓஔ";
+
+pub fn consonant_class(c: char) -> Option<ConsonantClass> {
+    [(VALLINAM, ConsonantClass::Vallinam), (MELLINAM, ConsonantClass::Mellinam),
+        (IDAIYINAM, ConsonantClass::Idaiyinam), (GRANTHA, ConsonantClass::Grantha)]
+        .iter()
+        .find(|(letters, _)| letters.contains(c))
+        .map(|(_, class)| *class)
+}
+
+pub fn vowel_length(vowel: char) -> Option<VowelLength> {
+    if KURIL.contains(vowel) {
+        Some(VowelLength::Kuril)
+    } else if NEDIL.contains(vowel) {
+        Some(VowelLength::Nedil)
+    } else {
+        None
+    }
+}
+
+//One எழுத்து of Unicode Tamil text, as the parser reads it: ஸ்ரீ and க்ஷ are one syllable
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Syllable<'a> {
+    pub text: &'a str,
+    pub start: usize, //byte span of the syllable in the text
+    pub end: usize,
+    pub kind: SyllableKind,
+    pub consonant: Option<&'a str>, //க, or the cluster of ஸ்ரீ and க்ஷ
+    pub vowel: Option<char>, //the vowel it carries, as the vowel letter (கொ: ஒ, க: அ, க்: none)
+}
+
+impl Syllable<'_> {
+    //Class of the consonant: clusters with a Grantha letter (க்ஷ, ஸ்ரீ) are Grantha
+    pub fn consonant_class(&self) -> Option<ConsonantClass> {
+        let consonant = self.consonant?;
+        if consonant.chars().any(|c| GRANTHA.contains(c)) {
+            Some(ConsonantClass::Grantha)
+        } else {
+            consonant.chars().next().and_then(consonant_class)
+        }
+    }
+
+    //Length of the vowel of an uyir or uyirmei
+    pub fn vowel_length(&self) -> Option<VowelLength> {
+        self.vowel.and_then(vowel_length)
+    }
+}
+
+impl fmt::Display for Syllable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.text)
+    }
+}
+
+//ா -> ஆ
+fn vowel_of_sign(sign: Option<char>) -> Option<char> {
+    let sign = sign?;
+    VOWEL_SIGNS.iter().find(|(_, s)| *s == sign).map(|(v, _)| *v)
+}
+
+//A consonant (text[..length]) and its sign
+fn consonant_syllable(text: &str, length: usize, sign: Option<char>) -> (SyllableKind, Option<&str>, Option<char>) {
+    match sign {
+        Some(PULLI) => (SyllableKind::Consonant, Some(&text[..length]), None),
+        None => (SyllableKind::ConsonantVowel, Some(&text[..length]), Some('அ')),
+        sign => (SyllableKind::ConsonantVowel, Some(&text[..length]), vowel_of_sign(sign)),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Syllables<'a> {
+    text: &'a str,
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Syllables<'a> {
+    type Item = Syllable<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        use TamilDetailedEntity::*;
+        let (rest, entity) = parse_entity(self.rest).ok()?;
+        let start = self.text.len() - self.rest.len();
+        let end = self.text.len() - rest.len();
+        let text = &self.text[start..end];
+        self.rest = rest;
+        let (kind, consonant, vowel) = match entity {
+            Vowel('ஃ') => (SyllableKind::Aytham, None, None),
+            Vowel(c) => (SyllableKind::Vowel, None, Some(c)),
+            Consonant(c) => consonant_syllable(text, c.len_utf8(), None),
+            SeparateEntity((c, m)) => consonant_syllable(text, c.len_utf8(), Some(m)),
+            ComposedEntity((c, _, m)) => consonant_syllable(text, c.len_utf8(), Some(m)),
+            SpecialEntity(special) | MarkedSpecialConsonant((special, _, _)) => {
+                //ஸ்ரீ is ஸ்ர with the sign of ஈ
+                let sign = match entity {
+                    MarkedSpecialConsonant((_, _, m)) => Some(m),
+                    _ => special.chars().last().filter(|&c| vowel_of_sign(Some(c)).is_some()),
+                };
+                consonant_syllable(text, text.len() - sign.map_or(0, char::len_utf8), sign)
+            }
+            Mark(_) | Digit(_) | Number(_) | Fraction(_) | Sign(_) | Other(_) => (SyllableKind::Other, None, None),
+        };
+        Some(Syllable { text, start, end, kind, consonant, vowel })
+    }
+}
+
+//Syllables of Unicode Tamil text, with their spans, in order. Every character of the text
+//is in exactly one syllable.
+pub fn syllables(text: &str) -> Syllables<'_> {
+    Syllables { text, rest: text }
+}
+
+//கி -> (க், இ), க -> (க், அ), க்ஷொ -> (க்ஷ், ஒ), ஸ்ரீ -> (ஸ்ர், ஈ).
+//None unless letter is a single uyirmei.
+pub fn decompose(letter: &str) -> Option<(String, char)> {
+    let mut parts = syllables(letter);
+    match (parts.next(), parts.next()) {
+        (Some(Syllable { kind: SyllableKind::ConsonantVowel, consonant: Some(consonant), vowel: Some(vowel), .. }), None) => {
+            Some((format!("{}{}", consonant, PULLI), vowel))
+        }
+        _ => None,
+    }
+}
+
+//(ம், ஊ) -> மூ, (க், அ) -> க, (ஸ்ர், ஈ) -> ஸ்ரீ. mei is one or more consonants with pulli,
+//vowel any vowel but ஃ. Reverse of decompose.
+pub fn compose(mei: &str, vowel: char) -> Option<String> {
+    if mei.is_empty() || !syllables(mei).all(|s| s.kind == SyllableKind::Consonant) || vowel == 'ஃ' || !VOWELS.contains(vowel) {
+        return None;
+    }
+    let consonant = mei.strip_suffix(PULLI)?;
+    match VOWEL_SIGNS.iter().find(|(v, _)| *v == vowel) {
+        Some((_, sign)) => Some(format!("{}{}", consonant, sign)),
+        None => Some(consonant.to_string()), //அ
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_syllables() {
+        let parts: Vec<(&str, SyllableKind, Option<&str>, Option<char>)> = syllables("தமிழ் கொ ஔஃ ஸ்ரீக்ஷா")
+            .map(|s| (s.text, s.kind, s.consonant, s.vowel))
+            .collect();
+        assert_eq!(parts, [
+            ("த", SyllableKind::ConsonantVowel, Some("த"), Some('அ')),
+            ("மி", SyllableKind::ConsonantVowel, Some("ம"), Some('இ')),
+            ("ழ்", SyllableKind::Consonant, Some("ழ"), None),
+            (" ", SyllableKind::Other, None, None),
+            ("கொ", SyllableKind::ConsonantVowel, Some("க"), Some('ஒ')),
+            (" ", SyllableKind::Other, None, None),
+            ("ஔ", SyllableKind::Vowel, None, Some('ஔ')),
+            ("ஃ", SyllableKind::Aytham, None, None),
+            (" ", SyllableKind::Other, None, None),
+            ("ஸ்ரீ", SyllableKind::ConsonantVowel, Some("ஸ்ர"), Some('ஈ')),
+            ("க்ஷா", SyllableKind::ConsonantVowel, Some("க்ஷ"), Some('ஆ')),
+        ]);
+        let syllable = syllables("அம்மா").nth(1).unwrap();
+        assert_eq!((syllable.start, syllable.end, syllable.to_string()), (3, 9, String::from("ம்")));
+        assert_eq!(serde_json::to_string(&syllable).unwrap(),
+            r#"{"text":"ம்","start":3,"end":9,"kind":"Consonant","consonant":"ம","vowel":null}"#);
+    }
+
+    #[test]
+    fn classifies_letters() {
+        let classes: Vec<_> = syllables("பண்ணை ஏழு ஜோக்ஷ")
+            .filter(|s| s.kind != SyllableKind::Other)
+            .map(|s| (s.kind, s.consonant_class(), s.vowel_length()))
+            .collect();
+        assert_eq!(classes, [
+            (SyllableKind::ConsonantVowel, Some(ConsonantClass::Vallinam), Some(VowelLength::Kuril)),
+            (SyllableKind::Consonant, Some(ConsonantClass::Mellinam), None),
+            (SyllableKind::ConsonantVowel, Some(ConsonantClass::Mellinam), Some(VowelLength::Nedil)),
+            (SyllableKind::Vowel, None, Some(VowelLength::Nedil)),
+            (SyllableKind::ConsonantVowel, Some(ConsonantClass::Idaiyinam), Some(VowelLength::Kuril)),
+            (SyllableKind::ConsonantVowel, Some(ConsonantClass::Grantha), Some(VowelLength::Nedil)),
+            (SyllableKind::ConsonantVowel, Some(ConsonantClass::Grantha), Some(VowelLength::Kuril)),
+        ]);
+    }
+
+    #[test]
+    fn composes_uyirmei() {
+        assert_eq!(decompose("கி"), Some((String::from("க்"), 'இ')));
+        assert_eq!(decompose("ஜ"), Some((String::from("ஜ்"), 'அ')));
+        assert_eq!(decompose("க்ஷொ"), Some((String::from("க்ஷ்"), 'ஒ')));
+        assert_eq!(decompose("ஸ்ரீ"), Some((String::from("ஸ்ர்"), 'ஈ')));
+        assert_eq!(decompose("க்"), None);
+        assert_eq!(decompose("கிக"), None);
+        assert_eq!(compose("ம்", 'ஊ').as_deref(), Some("மூ"));
+        assert_eq!(compose("ஶ்", 'அ').as_deref(), Some("ஶ"));
+        assert_eq!(compose("க்ஷ்", 'ஔ').as_deref(), Some("க்ஷௌ"));
+        assert_eq!(compose("ஸ்ர்", 'ஈ').as_deref(), Some("ஸ்ரீ"));
+        assert_eq!(compose("ம", 'ஊ'), None);
+        assert_eq!(compose("ம்", 'ஃ'), None);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::normalize::normalize;
+use crate::{convert_stmzh_unic, convert_unic_stmzh, parse_entity};
+use std::collections::BTreeMap;
+use std::fmt;
+
+//A syllable that doesn't come back unchanged after Unicode -> legacy -> Unicode
+#[derive(Debug)]
+pub struct RoundTripFailure {
+    pub kind: &'static str,
+    pub offset: usize, //byte offset of the syllable in the corpus
+    pub source: String,
+    pub encoded: String,
+    pub decoded: String,
+}
+
+#[derive(Debug, Default)]
+pub struct RoundTripReport {
+    pub syllables: usize,
+    pub failures: Vec<RoundTripFailure>,
+    pub text_round_trips: bool, //the whole corpus, converted in one go, comes back unchanged
+}
+
+impl RoundTripReport {
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty() && self.text_round_trips
+    }
+
+    //Number of failing syllables per entity kind
+    pub fn failing_kinds(&self) -> BTreeMap<&'static str, usize> {
+        let mut kinds = BTreeMap::new();
+        for failure in &self.failures {
+            *kinds.entry(failure.kind).or_insert(0) += 1;
+        }
+        kinds
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_ascii_graphic() || c == ' ' { c.to_string() } else { format!("<U+{:04X}>", c as u32) })
+        .collect()
+}
+
+impl fmt::Display for RoundTripReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} syllables, {} failing", self.syllables, self.failures.len())?;
+        if !self.text_round_trips {
+            writeln!(f, "the whole text does not round-trip")?;
+        }
+        for (kind, count) in self.failing_kinds() {
+            writeln!(f, "  {}: {}", kind, count)?;
+        }
+        for failure in &self.failures {
+            writeln!(f, "{}\t{}\t{} -> {} -> {}", failure.offset, failure.kind,
+                failure.source, escape(&failure.encoded), failure.decoded)?;
+        }
+        Ok(())
+    }
+}
+
+//Converts every syllable of corpus with encode then decode, and records the ones that differ.
+//Two neighbours that each round-trip alone are also checked together, since a decoder may read
+//their glyphs as a single letter (e.g. கெ followed by ள read back as கௌ).
+pub fn verify<E, D>(corpus: &str, encode: E, decode: D) -> RoundTripReport
+where
+    E: Fn(&str) -> String,
+    D: Fn(&str) -> String,
+{
+    let mut syllables = Vec::new();
+    let mut data = corpus;
+    while let Ok((rest, entity)) = parse_entity(data) {
+        let offset = corpus.len() - data.len();
+        syllables.push((entity.kind(), offset, offset + data.len() - rest.len()));
+        data = rest;
+    }
+
+    let round_trip = |kind, start, end| {
+        let source = &corpus[start..end];
+        let encoded = encode(source);
+        let decoded = decode(&encoded);
+        if decoded == normalize(source) {
+            return None;
+        }
+        Some(RoundTripFailure {
+            kind,
+            offset: start,
+            source: source.to_string(),
+            encoded,
+            decoded,
+        })
+    };
+    let alone: Vec<Option<RoundTripFailure>> = syllables.iter().map(|&(kind, start, end)| round_trip(kind, start, end)).collect();
+
+    let mut report = RoundTripReport {
+        syllables: syllables.len(),
+        ..Default::default()
+    };
+    let failing: Vec<bool> = alone.iter().map(Option::is_some).collect();
+    for (i, failure) in alone.into_iter().enumerate() {
+        let (kind, start, _) = syllables[i];
+        let failure = failure.or_else(|| match failing.get(i + 1) {
+            Some(false) => round_trip(kind, start, syllables[i + 1].2),
+            _ => None,
+        });
+        report.failures.extend(failure);
+    }
+    report.text_round_trips = decode(&encode(corpus)) == normalize(corpus);
+    report
+}
+
+pub fn verify_stmzh(corpus: &str) -> RoundTripReport {
+    verify(corpus, convert_unic_stmzh, convert_stmzh_unic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alphabet_round_trips() {
+        let report = verify_stmzh("அஆஇஈஉஊஎஏஐஒஓஔஃ கஙசஞ க்ங்ச் கிகீகுகூ காகெகேகைகொகோகௌ ஸ்ரீ க்ஷ");
+        assert!(report.is_ok(), "{}", report);
+    }
+
+    #[test]
+    fn reports_failing_kinds() {
+        let report = verify_stmzh("அகெள");
+        assert_eq!(report.syllables, 3);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].kind, "ComposedEntity");
+        assert_eq!(report.failures[0].source, "கெள");
+        assert_eq!(report.failures[0].decoded, "கௌ");
+        assert!(!report.text_round_trips);
+    }
+
+    #[test]
+    fn reports_only_failing_syllables() {
+        let report = verify_stmzh("௧௨ ஶ ௐ");
+        let offsets: Vec<usize> = report.failures.iter().map(|f| f.offset).collect();
+        assert_eq!(offsets, [0, 3, 7, 11]);
+        assert_eq!(report.failing_kinds().get("Other"), None);
+    }
+}
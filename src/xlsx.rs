@@ -0,0 +1,321 @@
+use crate::csv::{cell_column, Columns};
+use crate::fonts::FontConversion;
+use crate::package::{part_names, read_part, read_parts, rewrite_package};
+use crate::xml::{attribute, convert_text, ends_with, rewrite_attributes, rewrite_xml};
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, BytesText, Event};
+use std::collections::{HashMap, HashSet};
+
+fn is_sheet(name: &str) -> bool {
+    name.starts_with("xl/worksheets/") && name.ends_with(".xml")
+}
+
+#[derive(Debug, Default)]
+struct Cell {
+    column: Option<usize>,
+    row: Option<usize>,
+    style: usize,
+    kind: String,
+}
+
+fn read_cell(e: &BytesStart) -> Result<Cell, String> {
+    let reference = attribute(e, b"r")?.unwrap_or_default();
+    Ok(Cell {
+        column: cell_column(&reference),
+        row: reference.trim_start_matches(|c: char| c.is_ascii_uppercase()).parse().ok(),
+        style: attribute(e, b"s")?.and_then(|s| s.parse().ok()).unwrap_or(0),
+        kind: attribute(e, b"t")?.unwrap_or_default(),
+    })
+}
+
+//Columns of a sheet to convert, and whether its first row is a header to keep
+#[derive(Debug, Default)]
+struct SheetColumns {
+    selected: Vec<usize>,
+    skip_header: bool,
+}
+
+#[derive(Debug, Default)]
+struct Workbook {
+    source_fonts: Vec<bool>, //fonts of styles.xml: is it a source font
+    source_styles: Vec<bool>, //cell formats (cellXfs): is their font a source font
+    strings: Vec<String>, //text of the shared strings
+    sheets: HashMap<String, SheetColumns>,
+    in_place: HashSet<usize>, //shared strings converted where they are
+    appended: HashMap<usize, usize>, //shared strings also shown unconverted: index of their converted copy
+}
+
+impl Workbook {
+    fn read_styles(&mut self, xml: &str, conversion: &FontConversion) -> Result<(), String> {
+        let mut font_ids = Vec::new();
+        rewrite_xml(xml, |event, path| {
+            if let Event::Start(e) | Event::Empty(e) = &event {
+                match e.name().as_ref() {
+                    b"font" if ends_with(path, &[b"fonts"]) => self.source_fonts.push(false),
+                    b"name" if ends_with(path, &[b"fonts", b"font"]) => {
+                        let source = attribute(e, b"val")?.is_some_and(|name| conversion.is_source_font(&name));
+                        if let Some(font) = self.source_fonts.last_mut() {
+                            *font = source;
+                        }
+                    }
+                    b"xf" if ends_with(path, &[b"cellXfs"]) => {
+                        font_ids.push(attribute(e, b"fontId")?.and_then(|id| id.parse::<usize>().ok()).unwrap_or(0));
+                    }
+                    _ => (),
+                }
+            }
+            Ok(event)
+        })?;
+        self.source_styles = font_ids.iter().map(|id| self.source_fonts.get(*id).copied().unwrap_or(false)).collect();
+        Ok(())
+    }
+
+    fn read_strings(&mut self, xml: &str) -> Result<(), String> {
+        rewrite_xml(xml, |event, path| {
+            match &event {
+                Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"si" => self.strings.push(String::new()),
+                Event::Text(e) if ends_with(path, &[b"si", b"t"]) || ends_with(path, &[b"si", b"r", b"t"]) => {
+                    let text = e.unescape().map_err(|e| e.to_string())?;
+                    if let Some(string) = self.strings.last_mut() {
+                        string.push_str(&text);
+                    }
+                }
+                _ => (),
+            }
+            Ok(event)
+        })?;
+        Ok(())
+    }
+
+    fn converts(&self, cell: &Cell, columns: &SheetColumns) -> bool {
+        if columns.skip_header && cell.row == Some(1) {
+            return false;
+        }
+        cell.column.is_some_and(|c| columns.selected.contains(&c)) || self.source_styles.get(cell.style).copied().unwrap_or(false)
+    }
+
+    //Finds the columns of the sheet, and the shared strings its cells convert or keep
+    fn read_sheet(&mut self, name: &str, xml: &str, columns: &Columns, converted: &mut HashSet<usize>, kept: &mut HashSet<usize>) -> Result<(), String> {
+        let mut cells: Vec<(Cell, String)> = Vec::new();
+        rewrite_xml(xml, |event, path| {
+            match &event {
+                Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"c" => cells.push((read_cell(e)?, String::new())),
+                Event::Text(e) if ends_with(path, &[b"c", b"v"]) || ends_with(path, &[b"is", b"t"]) || ends_with(path, &[b"is", b"r", b"t"]) => {
+                    let text = e.unescape().map_err(|e| e.to_string())?;
+                    if let Some((_, value)) = cells.last_mut() {
+                        value.push_str(&text);
+                    }
+                }
+                _ => (),
+            }
+            Ok(event)
+        })?;
+
+        let mut header: Vec<String> = Vec::new();
+        for (cell, value) in cells.iter().filter(|(cell, _)| cell.row == Some(1)) {
+            if let Some(column) = cell.column {
+                if header.len() <= column {
+                    header.resize(column + 1, String::new());
+                }
+                header[column] = match cell.kind.as_str() {
+                    "s" => value.parse::<usize>().ok().and_then(|i| self.strings.get(i)).cloned().unwrap_or_default(),
+                    _ => value.clone(),
+                };
+            }
+        }
+        let sheet_columns = SheetColumns { selected: columns.find(&header), skip_header: columns.uses_header(&header) };
+        for (cell, value) in &cells {
+            if let ("s", Ok(index)) = (cell.kind.as_str(), value.parse::<usize>()) {
+                if self.converts(cell, &sheet_columns) { converted.insert(index) } else { kept.insert(index) };
+            }
+        }
+        self.sheets.insert(name.to_string(), sheet_columns);
+        Ok(())
+    }
+
+    fn rewrite_styles(&self, xml: &str, conversion: &FontConversion) -> Result<String, String> {
+        let mut font = None;
+        rewrite_xml(xml, |event, path| {
+            if let Event::Start(e) | Event::Empty(e) = &event {
+                if e.name().as_ref() == b"font" && ends_with(path, &[b"fonts"]) {
+                    font = Some(font.map_or(0, |f| f + 1));
+                }
+            }
+            Ok(match event {
+                Event::Empty(e) if ends_with(path, &[b"fonts", b"font"]) && font.is_some_and(|f| self.source_fonts[f]) => {
+                    match e.name().as_ref() {
+                        b"name" => Event::Empty(rewrite_attributes(&e, |key, _| {
+                            if key == b"val" { Some(conversion.target_font().to_string()) } else { None }
+                        })?.unwrap_or(e)),
+                        //a symbol charset would keep Excel from shaping the Unicode text
+                        b"charset" => Event::Text(BytesText::new("")),
+                        _ => Event::Empty(e),
+                    }
+                }
+                event => event,
+            })
+        })
+    }
+
+    fn rewrite_strings(&self, xml: &str, conversion: &FontConversion) -> Result<String, String> {
+        let mut index: Option<usize> = None;
+        let mut run_source = false;
+        let mut xml = rewrite_xml(xml, |event, path| {
+            Ok(match event {
+                Event::Start(e) if e.name().as_ref() == b"sst" && !self.appended.is_empty() => {
+                    let count = attribute(&e, b"uniqueCount")?.and_then(|c| c.parse::<usize>().ok());
+                    match count {
+                        Some(count) => Event::Start(rewrite_attributes(&e, |key, _| {
+                            if key == b"uniqueCount" { Some((count + self.appended.len()).to_string()) } else { None }
+                        })?.unwrap_or(e)),
+                        None => Event::Start(e),
+                    }
+                }
+                Event::Start(e) if e.name().as_ref() == b"si" => {
+                    index = Some(index.map_or(0, |i| i + 1));
+                    Event::Start(e)
+                }
+                Event::Start(e) if e.name().as_ref() == b"r" => {
+                    run_source = false;
+                    Event::Start(e)
+                }
+                Event::Empty(e) if e.name().as_ref() == b"rFont" && ends_with(path, &[b"r", b"rPr"]) => {
+                    run_source = attribute(&e, b"val")?.is_some_and(|font| conversion.is_source_font(&font));
+                    if run_source {
+                        Event::Empty(rewrite_attributes(&e, |key, _| {
+                            if key == b"val" { Some(conversion.target_font().to_string()) } else { None }
+                        })?.unwrap_or(e))
+                    } else {
+                        Event::Empty(e)
+                    }
+                }
+                Event::Text(e) if ends_with(path, &[b"si", b"t"]) || ends_with(path, &[b"si", b"r", b"t"]) => {
+                    let in_run = ends_with(path, &[b"r", b"t"]);
+                    if index.is_some_and(|i| self.in_place.contains(&i)) || (in_run && run_source) {
+                        convert_text(&e, |text| conversion.convert(text))?
+                    } else {
+                        Event::Text(e)
+                    }
+                }
+                event => event,
+            })
+        })?;
+
+        let mut copies: Vec<(usize, usize)> = self.appended.iter().map(|(from, to)| (*to, *from)).collect();
+        copies.sort();
+        let appended: String = copies.iter()
+            .map(|(_, from)| format!("<si><t xml:space=\"preserve\">{}</t></si>", escape(conversion.convert(&self.strings[*from]).as_str())))
+            .collect();
+        if let Some(end) = xml.rfind("</sst>") {
+            xml.insert_str(end, &appended);
+        }
+        Ok(xml)
+    }
+
+    fn rewrite_sheet(&self, name: &str, xml: &str, conversion: &FontConversion) -> Result<String, String> {
+        let columns = &self.sheets[name];
+        let mut cell: Option<(bool, String)> = None; //does the cell convert, and its type
+        rewrite_xml(xml, |event, path| {
+            Ok(match event {
+                Event::Start(e) if e.name().as_ref() == b"c" => {
+                    let c = read_cell(&e)?;
+                    cell = Some((self.converts(&c, columns), c.kind));
+                    Event::Start(e)
+                }
+                Event::Text(e) if ends_with(path, &[b"c", b"v"]) => match &cell {
+                    Some((true, kind)) if kind == "s" => {
+                        let index = e.unescape().map_err(|e| e.to_string())?.trim().parse::<usize>().ok();
+                        match index.and_then(|i| self.appended.get(&i)) {
+                            Some(copy) => Event::Text(BytesText::new(&copy.to_string()).into_owned()),
+                            None => Event::Text(e),
+                        }
+                    }
+                    _ => Event::Text(e),
+                },
+                Event::Text(e) if ends_with(path, &[b"is", b"t"]) || ends_with(path, &[b"is", b"r", b"t"]) => match &cell {
+                    Some((true, _)) => convert_text(&e, |text| conversion.convert(text))?,
+                    _ => Event::Text(e),
+                },
+                event => event,
+            })
+        })
+    }
+}
+
+//Converts the text cells of the given columns of every sheet, and the cells whose format
+//sets a source font. Columns given by their header name are looked for in the first row,
+//which is then kept as it is. Runs of rich text set in a source font are converted too, and
+//source fonts are changed to the target font. A shared string shown both in converted and
+//kept cells gets a converted copy. Formats and everything else are kept.
+pub fn convert_xlsx(input: &[u8], columns: &Columns, conversion: &FontConversion) -> Result<Vec<u8>, String> {
+    let conversion = &conversion.for_document(&read_parts(input, |name| name == "xl/sharedStrings.xml" || is_sheet(name))?);
+    let mut workbook = Workbook::default();
+    if let Some(xml) = read_part(input, "xl/styles.xml")? {
+        workbook.read_styles(&xml, conversion)?;
+    }
+    if let Some(xml) = read_part(input, "xl/sharedStrings.xml")? {
+        workbook.read_strings(&xml)?;
+    }
+    let (mut converted, mut kept) = (HashSet::new(), HashSet::new());
+    for name in part_names(input)?.iter().filter(|name| is_sheet(name)) {
+        if let Some(xml) = read_part(input, name)? {
+            workbook.read_sheet(name, &xml, columns, &mut converted, &mut kept)?;
+        }
+    }
+    let mut shared: Vec<usize> = converted.into_iter().filter(|i| *i < workbook.strings.len()).collect();
+    shared.sort();
+    for index in shared {
+        if kept.contains(&index) {
+            let copy = workbook.strings.len() + workbook.appended.len();
+            workbook.appended.insert(index, copy);
+        } else {
+            workbook.in_place.insert(index);
+        }
+    }
+
+    rewrite_package(input, |name, contents| {
+        if name != "xl/styles.xml" && name != "xl/sharedStrings.xml" && !is_sheet(name) {
+            return Ok(None);
+        }
+        let xml = std::str::from_utf8(contents).map_err(|e| e.to_string())?;
+        let xml = match name {
+            "xl/styles.xml" => workbook.rewrite_styles(xml, conversion)?,
+            "xl/sharedStrings.xml" => workbook.rewrite_strings(xml, conversion)?,
+            _ => workbook.rewrite_sheet(name, xml, conversion)?,
+        };
+        Ok(Some(xml.into_bytes()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::fonts::Direction;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+
+    const STYLES: &str = r#"<styleSheet><fonts count="2"><font><name val="Calibri"/></font><font><sz val="11"/><name val="STMZH"/><charset val="2"/></font></fonts><cellXfs count="2"><xf fontId="0"/><xf fontId="1"/></cellXfs></styleSheet>"#;
+    const STRINGS: &str = r#"<sst count="4" uniqueCount="3"><si><t>Name</t></si><si><t>&#xF0D8;&#xF0EF;&#xF056;</t></si><si><r><t>A </t></r><r><rPr><rFont val="STMZH"/></rPr><t>&#xF0EF;</t></r></si></sst>"#;
+    const SHEET: &str = r#"<worksheet><sheetData><row r="1"><c r="A1" t="s"><v>0</v></c><c r="B1" t="s"><v>1</v></c></row><row r="2"><c r="A2" t="s"><v>1</v></c><c r="B2" s="1" t="inlineStr"><is><t>&#xF0EF;</t></is></c><c r="C2" t="s"><v>2</v></c></row></sheetData></worksheet>"#;
+
+    fn sample_xlsx() -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, xml) in &[("xl/styles.xml", STYLES), ("xl/sharedStrings.xml", STRINGS), ("xl/worksheets/sheet1.xml", SHEET)] {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(xml.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn converts_selected_columns_and_legacy_fonts() {
+        let conversion = FontConversion::new(Encoding::stmzh(), Direction::Decode);
+        let output = convert_xlsx(&sample_xlsx(), &Columns::parse("Name"), &conversion).unwrap();
+        let part = |name| read_part(&output, name).unwrap().unwrap();
+        //string 1 is shown in the Name column and in the header: it gets a converted copy
+        assert_eq!(part("xl/sharedStrings.xml"), r#"<sst count="4" uniqueCount="4"><si><t>Name</t></si><si><t>&#xF0D8;&#xF0EF;&#xF056;</t></si><si><r><t>A </t></r><r><rPr><rFont val="Latha"/></rPr><t>க</t></r></si><si><t xml:space="preserve">கொ</t></si></sst>"#);
+        assert!(part("xl/worksheets/sheet1.xml").contains(r#"<c r="A2" t="s"><v>3</v></c><c r="B2" s="1" t="inlineStr"><is><t>க</t></is></c>"#));
+        assert!(part("xl/styles.xml").contains(r#"<font><sz val="11"/><name val="Latha"/></font>"#));
+    }
+}
@@ -0,0 +1,93 @@
+use quick_xml::events::{BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+
+//Copies xml event by event, letting rewrite change them. rewrite gets the names of the open
+//elements: the parents of the event's element (its own name is in the event).
+pub fn rewrite_xml<F>(xml: &str, mut rewrite: F) -> Result<String, String>
+where
+    F: for<'a> FnMut(Event<'a>, &[Vec<u8>]) -> Result<Event<'a>, String>,
+{
+    let mut reader = Reader::from_str(xml);
+    let mut writer = Writer::new(Vec::with_capacity(xml.len()));
+    let mut path: Vec<Vec<u8>> = Vec::new();
+    loop {
+        let event = reader.read_event().map_err(|e| format!("{} (at byte {})", e, reader.buffer_position()))?;
+        match &event {
+            Event::Eof => break,
+            Event::End(_) => {
+                path.pop();
+            }
+            _ => (),
+        }
+        let name = match &event {
+            Event::Start(e) => Some(e.name().as_ref().to_vec()),
+            _ => None,
+        };
+        writer.write_event(rewrite(event, &path)?).map_err(|e| e.to_string())?;
+        if let Some(name) = name {
+            path.push(name);
+        }
+    }
+    String::from_utf8(writer.into_inner()).map_err(|e| e.to_string())
+}
+
+//Does path end with the elements of tail
+pub fn ends_with(path: &[Vec<u8>], tail: &[&[u8]]) -> bool {
+    path.len() >= tail.len() && path[path.len() - tail.len()..].iter().zip(tail).all(|(a, b)| a == b)
+}
+
+//Unescaped value of the attribute key
+pub fn attribute(e: &BytesStart, key: &[u8]) -> Result<Option<String>, String> {
+    for attribute in e.attributes() {
+        let attribute = attribute.map_err(|e| e.to_string())?;
+        if attribute.key.as_ref() == key {
+            return attribute.unescape_value().map(|v| Some(v.into_owned())).map_err(|e| e.to_string());
+        }
+    }
+    Ok(None)
+}
+
+//Same element, with the attributes for which rewrite returns a value changed to it
+//(None if none has changed)
+pub fn rewrite_attributes<F>(e: &BytesStart, mut rewrite: F) -> Result<Option<BytesStart<'static>>, String>
+where
+    F: FnMut(&[u8], &str) -> Option<String>,
+{
+    let mut changed = false;
+    let mut attributes = Vec::new();
+    for attribute in e.attributes() {
+        let attribute = attribute.map_err(|e| e.to_string())?;
+        let value = attribute.unescape_value().map_err(|e| e.to_string())?;
+        let key = attribute.key.as_ref().to_vec();
+        match rewrite(&key, &value) {
+            Some(new) => {
+                changed = true;
+                attributes.push((key, new));
+            }
+            None => attributes.push((key, value.into_owned())),
+        }
+    }
+    if !changed {
+        return Ok(None);
+    }
+    let mut element = BytesStart::new(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+    for (key, value) in &attributes {
+        element.push_attribute((key.as_slice(), value.as_bytes()));
+    }
+    Ok(Some(element))
+}
+
+pub fn convert_text<F>(e: &BytesText, convert: F) -> Result<Event<'static>, String>
+where
+    F: FnOnce(&str) -> String,
+{
+    let text = e.unescape().map_err(|e| e.to_string())?;
+    Ok(Event::Text(BytesText::new(&convert(&text)).into_owned()))
+}
+
+//Same element, without the attribute key
+pub fn without_attribute(e: &BytesStart, key: &[u8]) -> BytesStart<'static> {
+    let mut element = BytesStart::new(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+    element.extend_attributes(e.attributes().flatten().filter(|a| a.key.as_ref() != key));
+    element.into_owned()
+}